@@ -0,0 +1,83 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use banded_global_alignment::{backtrack_banded_alignment, construct_banded_matrix};
+use fasta_reader::read_fasta;
+use needleman_wunsch::{backtrack_alignment, construct_matrix, Alignment};
+use scoring::SimpleScoring;
+
+/// The traceback as it was before: every character is inserted at the front of the alignment, which takes quadratic time
+fn backtrack_insert_front(score: impl Fn(usize, usize) -> Option<i32>, seq1: &[u8], seq2: &[u8], gap_score: i32) -> Alignment {
+    let (mut current_row, mut current_col) = (seq2.len(), seq1.len());
+    let mut aligned_seq1: Vec<u8> = vec![];
+    let mut aligned_seq2: Vec<u8> = vec![];
+    let mut diff_line: Vec<u8> = vec![];
+
+    while current_col != 0 || current_row != 0 {
+        let current_score = score(current_row, current_col).expect("The alignment stays inside the matrix");
+        if current_col != 0 && score(current_row, current_col - 1).is_some_and(|score| current_score == score + gap_score) {
+            aligned_seq1.insert(0, seq1[current_col - 1]);
+            diff_line.insert(0, b' ');
+            aligned_seq2.insert(0, b'-');
+            current_col -= 1;
+        } else if current_row != 0 && score(current_row - 1, current_col).is_some_and(|score| current_score == score + gap_score) {
+            aligned_seq1.insert(0, b'-');
+            diff_line.insert(0, b' ');
+            aligned_seq2.insert(0, seq2[current_row - 1]);
+            current_row -= 1;
+        } else {
+            aligned_seq1.insert(0, seq1[current_col - 1]);
+            aligned_seq2.insert(0, seq2[current_row - 1]);
+            diff_line.insert(0, if seq1[current_col - 1] == seq2[current_row - 1] { b'|' } else { b'*' });
+            current_row -= 1;
+            current_col -= 1;
+        }
+    }
+    (aligned_seq1, diff_line, aligned_seq2)
+}
+
+/// A copy of seq1 with about 1 in 100 characters substituted, deleted or followed by an insertion
+fn mutate(rng: &mut StdRng, seq1: &[u8]) -> Vec<u8> {
+    let mut seq2 = Vec::with_capacity(seq1.len());
+    for &char1 in seq1 {
+        match rng.gen_range(0..100) {
+            0 => seq2.push(b"ACGT"[rng.gen_range(0..4)]),
+            1 => {}
+            2 => seq2.extend([char1, b"ACGT"[rng.gen_range(0..4)]]),
+            _ => seq2.push(char1),
+        }
+    }
+    seq2
+}
+
+/// Compare the traceback that reverses the alignment once with inserting at the front, on the full Needleman-Wunsch
+/// matrix of `tests/verylarge.fasta` and on banded matrices of longer sequences
+fn bench_traceback(c: &mut Criterion) {
+    let scoring = SimpleScoring::new(1, -1, -3);
+    let mut group = c.benchmark_group("traceback");
+    group.sample_size(10);
+
+    let (seq1, seq2) = read_fasta("../tests/verylarge.fasta").expect("The benchmark input can be read");
+    let (seq1, seq2) = (seq1.into_bytes(), seq2.into_bytes());
+    let matrix = construct_matrix(&seq1, &seq2, &scoring);
+    group.bench_function(BenchmarkId::new("reverse", "verylarge"), |b| b.iter(|| backtrack_alignment(&matrix, &seq1, &seq2, &scoring)));
+    group.bench_function(BenchmarkId::new("insert_front", "verylarge"), |b| {
+        b.iter(|| backtrack_insert_front(|row, col| Some(matrix[(row, col)]), &seq1, &seq2, scoring.gap_score))
+    });
+
+    let mut rng = StdRng::seed_from_u64(42);
+    for len in [10_000, 100_000] {
+        let seq1: Vec<u8> = (0..len).map(|_| b"ACGT"[rng.gen_range(0..4)]).collect();
+        let seq2 = mutate(&mut rng, &seq1);
+        let matrix = construct_banded_matrix(&seq1, &seq2, &scoring, 256);
+        group.bench_with_input(BenchmarkId::new("reverse", len), &matrix, |b, matrix| b.iter(|| backtrack_banded_alignment(matrix, &seq1, &seq2, &scoring)));
+        group.bench_with_input(BenchmarkId::new("insert_front", len), &matrix, |b, matrix| {
+            b.iter(|| backtrack_insert_front(|row, col| matrix.get(row, col), &seq1, &seq2, scoring.gap_score))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_traceback);
+criterion_main!(benches);
@@ -0,0 +1,116 @@
+use std::cmp::max;
+
+use scoring::Scoring;
+
+use crate::{backtrack_banded_alignment, construct_banded_matrix, BandedAlignment};
+
+/// The width the band starts with, unless the difference in length of the sequences is larger
+const INITIAL_WIDTH: usize = 8;
+
+/// Upper bound on the score of any global alignment that leaves the band of the given width,
+/// or `None` if no alignment can leave the band.
+/// Such an alignment reaches diagonal `width + 1` on one side, and has to return to the diagonal of the bottom right corner,
+/// so it contains at least `2 * (width + 1) - length_difference` gaps.
+fn outside_band_bound(seq1_len: usize, seq2_len: usize, width: usize, max_substitution_score: i32, gap_score: i32) -> Option<i64> {
+    let min_gaps = 2 * (width + 1) - seq1_len.abs_diff(seq2_len);
+    let total_len = seq1_len + seq2_len;
+    if min_gaps > total_len {
+        return None;
+    }
+
+    // an alignment with k aligned pairs has `total_len - 2 * k` gaps, so the bound is linear in k and maximal at one of both ends
+    let max_aligned = (total_len - min_gaps) / 2;
+    let bound = |aligned: usize| aligned as i64 * max_substitution_score as i64 + (total_len - 2 * aligned) as i64 * gap_score as i64;
+    Some(max(bound(0), bound(max_aligned)))
+}
+
+/// The highest score of aligning any character of seq1 with any character of seq2
+fn max_substitution_score<S: Scoring + ?Sized>(seq1: &[u8], seq2: &[u8], scoring: &S) -> i32 {
+    let mut seq1_alphabet = [false; 256];
+    let mut seq2_alphabet = [false; 256];
+    seq1.iter().for_each(|&c| seq1_alphabet[c as usize] = true);
+    seq2.iter().for_each(|&c| seq2_alphabet[c as usize] = true);
+
+    let mut max_score = i32::MIN;
+    for a in (0..=255).filter(|&a| seq1_alphabet[a as usize]) {
+        for b in (0..=255).filter(|&b| seq2_alphabet[b as usize]) {
+            max_score = max(max_score, scoring.substitution_score(a, b));
+        }
+    }
+    max_score
+}
+
+/// Calculate the optimal global alignment of seq1 and seq2 with a banded alignment whose width is doubled
+/// until no alignment outside the band can have a higher score (Ukkonen, Fickett).
+/// The band starts with `initial_width`, or a small width if it is `None`.
+/// The returned alignment contains the width of the last band.
+pub fn adaptive_banded_align<S: Scoring + ?Sized>(seq1: &[u8], seq2: &[u8], scoring: &S, initial_width: Option<usize>) -> BandedAlignment {
+    let max_substitution_score = max_substitution_score(seq1, seq2, scoring);
+    let full_width = max(seq1.len(), seq2.len());
+    // the bottom right corner must be inside the band
+    let mut width = max(initial_width.unwrap_or(INITIAL_WIDTH), seq1.len().abs_diff(seq2.len()));
+
+    loop {
+        let matrix = construct_banded_matrix(seq1, seq2, scoring, width);
+        let score = matrix.score().expect("The bottom right corner is inside the band");
+        let is_optimal = width >= full_width || outside_band_bound(seq1.len(), seq2.len(), width, max_substitution_score, scoring.gap_score())
+            .is_none_or(|bound| score as i64 >= bound);
+        if is_optimal {
+            let alignment = backtrack_banded_alignment(&matrix, seq1, seq2, scoring);
+            return BandedAlignment { score, width, alignment };
+        }
+        width = max(2 * width, 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use fasta_reader::read_fasta;
+    use needleman_wunsch::{construct_matrix, score_alignment};
+    use scoring::{SimpleScoring, SubstitutionMatrix};
+
+    use crate::adaptive::{adaptive_banded_align, outside_band_bound};
+
+    #[test]
+    fn test_outside_band_bound() {
+        // leaving a band of width 1 requires at least 4 gaps: 4 matches and 4 gaps at best
+        assert_eq!(outside_band_bound(6, 6, 1, 1, -1), Some(4 - 4));
+        // no alignment can leave a band that covers the whole matrix
+        assert_eq!(outside_band_bound(3, 3, 3, 1, -1), None);
+    }
+
+    #[test]
+    fn test_adaptive_banded_align() -> Result<(), Box<dyn Error>> {
+        let (seq1, seq2) = read_fasta("../tests/verylarge.fasta")?;
+        let seq1_chars = seq1.into_bytes();
+        let seq2_chars = seq2.into_bytes();
+
+        for scoring in [SimpleScoring::new(1, -1, -3), SimpleScoring::new(2, -1, -1)] {
+            let matrix = construct_matrix(&seq1_chars, &seq2_chars, &scoring);
+            let res = adaptive_banded_align(&seq1_chars, &seq2_chars, &scoring, None);
+            assert_eq!(res.score, matrix[(seq2_chars.len(), seq1_chars.len())]);
+            assert_eq!(score_alignment(&res.alignment.0, &res.alignment.2, &scoring), res.score);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_adaptive_banded_align_similar_sequences() -> Result<(), Box<dyn Error>> {
+        let (seq1, seq2) = read_fasta("../tests/protein_input.fasta")?;
+        let seq1_chars = seq1.into_bytes();
+        let seq2_chars = seq2.into_bytes();
+        let scoring = SubstitutionMatrix::blosum62(-4);
+
+        let matrix = construct_matrix(&seq1_chars, &seq2_chars, &scoring);
+        let res = adaptive_banded_align(&seq1_chars, &seq2_chars, &scoring, Some(1));
+        assert_eq!(res.score, matrix[(seq2_chars.len(), seq1_chars.len())]);
+
+        // an exact match never needs more than the initial band
+        let res = adaptive_banded_align(&seq1_chars, &seq1_chars, &SimpleScoring::new(1, -1, -1), Some(1));
+        assert_eq!(res.width, 1);
+        assert_eq!(res.alignment.0, seq1_chars);
+        Ok(())
+    }
+}
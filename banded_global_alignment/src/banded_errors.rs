@@ -0,0 +1,18 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+/// Error indicating that the band is too narrow to contain the bottom right corner of the matrix,
+/// so no global alignment can be found within the band
+#[derive(Debug, PartialEq, Eq)]
+pub struct CornerOutsideBandError {
+    pub width: usize,
+    pub length_difference: usize,
+}
+
+impl Display for CornerOutsideBandError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The sequences differ {} characters in length, which does not fit in a band of width {}", self.length_difference, self.width)
+    }
+}
+
+impl Error for CornerOutsideBandError {}
@@ -0,0 +1,205 @@
+use std::cmp::{max, min};
+
+use needleman_wunsch::{Alignment, AlignmentBuilder, DpMatrix};
+use scoring::Scoring;
+
+pub use crate::adaptive::adaptive_banded_align;
+pub use crate::banded_errors::CornerOutsideBandError;
+
+mod adaptive;
+mod banded_errors;
+
+/// Stand-in for the cells that are not calculated, which can't overflow when a gap score is added to it
+const NEG_INF: i32 = i32::MIN / 2;
+
+/// The cells of the Needleman-Wunsch matrix that are at most `width` diagonals away from the main diagonal,
+/// with seq2 along the rows and seq1 along the columns.
+/// Only the band is stored: row r of `cells` holds the `2 * width + 1` cells from column `r - width` up to column `r + width`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BandedMatrix {
+    width: usize,
+    cols: usize,
+    cells: DpMatrix,
+}
+
+impl BandedMatrix {
+    fn new(rows: usize, cols: usize, width: usize) -> BandedMatrix {
+        BandedMatrix { width, cols, cells: DpMatrix::new(rows, 2 * width + 1, NEG_INF) }
+    }
+
+    /// The position of cell (row, col) in `cells`, if that cell is in the band
+    fn index(&self, row: usize, col: usize) -> Option<(usize, usize)> {
+        if row >= self.cells.rows() || col >= self.cols || col + self.width < row || col > row + self.width {
+            return None;
+        }
+        Some((row, col + self.width - row))
+    }
+
+    /// The score in cell (row, col), or `None` if the cell is outside the band
+    pub fn get(&self, row: usize, col: usize) -> Option<i32> {
+        self.index(row, col).map(|index| self.cells[index]).filter(|&score| score != NEG_INF)
+    }
+
+    fn set(&mut self, row: usize, col: usize, score: i32) {
+        let index = self.index(row, col).expect("Cell is inside the band");
+        self.cells[index] = score;
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// The score of the optimal alignment within the band: the score in the bottom right corner
+    pub fn score(&self) -> Option<i32> {
+        self.get(self.cells.rows() - 1, self.cols - 1)
+    }
+}
+
+/// The optimal global alignment within the band, together with its score
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BandedAlignment {
+    pub score: i32,
+    /// The width of the band the alignment was found in
+    pub width: usize,
+    pub alignment: Alignment,
+}
+
+/// Fill in the cells of the Needleman-Wunsch matrix that lie in the band of the given width
+pub fn construct_banded_matrix<S: Scoring + ?Sized>(seq1: &[u8], seq2: &[u8], scoring: &S, width: usize) -> BandedMatrix {
+    let gap_score = scoring.gap_score();
+    let mut matrix = BandedMatrix::new(seq2.len() + 1, seq1.len() + 1, width);
+
+    // initialize the first column and first row
+    for col in 0..=min(seq1.len(), width) {
+        matrix.set(0, col, col as i32 * gap_score);
+    }
+    for row in 1..=min(seq2.len(), width) {
+        matrix.set(row, 0, row as i32 * gap_score);
+    }
+
+    // fill in the band
+    for row in 1..=seq2.len() {
+        let min_col = max(1, row as i32 - width as i32) as usize;
+        let max_col = min(seq1.len(), row + width);
+        let current_seq2_char = seq2[row - 1];
+        for col in min_col..=max_col {
+            let diag_score = matrix.get(row - 1, col - 1).map_or(NEG_INF, |score| score + scoring.substitution_score(seq1[col - 1], current_seq2_char));
+            let col_score = matrix.get(row, col - 1).map_or(NEG_INF, |score| score + gap_score);
+            let row_score = matrix.get(row - 1, col).map_or(NEG_INF, |score| score + gap_score);
+
+            matrix.set(row, col, max(max(diag_score, col_score), row_score));
+        }
+    }
+
+    matrix
+}
+
+/// Find an optimal alignment in a banded matrix, the bottom right corner of the matrix must be inside the band
+pub fn backtrack_banded_alignment<S: Scoring + ?Sized>(matrix: &BandedMatrix, seq1: &[u8], seq2: &[u8], scoring: &S) -> Alignment {
+    let gap_score = scoring.gap_score();
+    let mut current_row = seq2.len();
+    let mut current_col = seq1.len();
+
+    let mut alignment = AlignmentBuilder::with_capacity(seq1.len() + seq2.len());
+
+    while current_col != 0 || current_row != 0 {
+        let current_score = matrix.get(current_row, current_col).expect("The alignment stays inside the band");
+        if current_col != 0 && matrix.get(current_row, current_col - 1).is_some_and(|score| current_score == score + gap_score) {
+            alignment.push_gap_in_seq2(seq1[current_col - 1]);
+            current_col -= 1;
+        } else if current_row != 0 && matrix.get(current_row - 1, current_col).is_some_and(|score| current_score == score + gap_score) {
+            alignment.push_gap_in_seq1(seq2[current_row - 1]);
+            current_row -= 1;
+        } else {
+            // diagonal case
+            alignment.push_aligned(seq1[current_col - 1], seq2[current_row - 1]);
+            current_row -= 1;
+            current_col -= 1;
+        }
+    }
+
+    alignment.finish()
+}
+
+/// Calculate the optimal global alignment of seq1 and seq2 that stays within `width` diagonals of the main diagonal,
+/// using O(n * width) time and memory.
+/// This is the optimal global alignment if the band is wide enough.
+pub fn banded_align<S: Scoring + ?Sized>(seq1: &[u8], seq2: &[u8], scoring: &S, width: usize) -> Result<BandedAlignment, CornerOutsideBandError> {
+    let length_difference = seq1.len().abs_diff(seq2.len());
+    if length_difference > width {
+        return Err(CornerOutsideBandError { width, length_difference });
+    }
+
+    let matrix = construct_banded_matrix(seq1, seq2, scoring, width);
+    let score = matrix.score().expect("The bottom right corner is inside the band");
+    let alignment = backtrack_banded_alignment(&matrix, seq1, seq2, scoring);
+    Ok(BandedAlignment { score, width, alignment })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use fasta_reader::read_fasta;
+    use needleman_wunsch::{construct_matrix, score_alignment};
+    use scoring::SimpleScoring;
+
+    use crate::{banded_align, construct_banded_matrix, CornerOutsideBandError};
+
+    #[test]
+    fn test_banded_matrix() -> Result<(), Box<dyn Error>> {
+        let (seq1, seq2) = read_fasta("../tests/input.fasta")?;
+        let seq1_chars = seq1.into_bytes();
+        let seq2_chars = seq2.into_bytes();
+        let scoring = SimpleScoring::new(1, -1, -1);
+
+        let matrix = construct_banded_matrix(&seq1_chars, &seq2_chars, &scoring, 2);
+        assert_eq!(matrix.get(0, 2), Some(-2));
+        assert_eq!(matrix.get(0, 3), None);
+        assert_eq!(matrix.get(5, 2), None);
+
+        // the cells inside the band of width 7 are the whole matrix
+        let full_matrix = construct_matrix(&seq1_chars, &seq2_chars, &scoring);
+        let banded_matrix = construct_banded_matrix(&seq1_chars, &seq2_chars, &scoring, 7);
+        for (row, full_row) in full_matrix.iter_rows().enumerate() {
+            for (col, &score) in full_row.iter().enumerate() {
+                assert_eq!(banded_matrix.get(row, col), Some(score));
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_banded_align() -> Result<(), Box<dyn Error>> {
+        let (seq1, seq2) = read_fasta("../tests/verylarge.fasta")?;
+        let seq1_chars = seq1.into_bytes();
+        let seq2_chars = seq2.into_bytes();
+        let scoring = SimpleScoring::new(1, -1, -3);
+
+        let matrix = construct_matrix(&seq1_chars, &seq2_chars, &scoring);
+        let optimal_score = matrix[(seq2_chars.len(), seq1_chars.len())];
+        let length_difference = seq1_chars.len().abs_diff(seq2_chars.len());
+        let max_width = seq1_chars.len().max(seq2_chars.len());
+        for width in [length_difference, length_difference + 10, length_difference + 100, max_width] {
+            let res = banded_align(&seq1_chars, &seq2_chars, &scoring, width)?;
+            let (aligned_seq1, diff_line, aligned_seq2) = &res.alignment;
+            assert_eq!(aligned_seq1.len(), diff_line.len());
+            assert_eq!(aligned_seq1.iter().filter(|&&c| c != b'-').copied().collect::<Vec<u8>>(), seq1_chars);
+            assert_eq!(aligned_seq2.iter().filter(|&&c| c != b'-').copied().collect::<Vec<u8>>(), seq2_chars);
+            assert_eq!(score_alignment(aligned_seq1, aligned_seq2, &scoring), res.score);
+            assert!(res.score <= optimal_score);
+        }
+        assert_eq!(banded_align(&seq1_chars, &seq2_chars, &scoring, max_width)?.score, optimal_score);
+        Ok(())
+    }
+
+    #[test]
+    fn test_banded_align_corner_outside_band() {
+        let scoring = SimpleScoring::new(1, -1, -1);
+        assert_eq!(banded_align(b"ACGTACGT", b"ACG", &scoring, 4), Err(CornerOutsideBandError { width: 4, length_difference: 5 }));
+
+        let res = banded_align(b"", b"ACG", &scoring, 3).unwrap();
+        assert_eq!(res.score, -3);
+        assert_eq!(res.alignment, (b"---".to_vec(), b"   ".to_vec(), b"ACG".to_vec()));
+    }
+}
@@ -0,0 +1,30 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use bitpal::{bitpal, bitpal_batch};
+use scoring::SimpleScoring;
+
+/// Compare the throughput of the scalar BitPAl with the 4-lane version on many pairs of sequences
+fn bench_bitpal(c: &mut Criterion) {
+    let mut rng = StdRng::seed_from_u64(42);
+    let scoring = SimpleScoring::new(1, -1, -3);
+    let mut group = c.benchmark_group("bitpal");
+
+    for len in [64, 256, 1024] {
+        // the test helpers are not available to benchmarks, so the random DNA sequences are generated here
+        let mut generate_sequence = || (0..len).map(|_| b"ACGT"[rng.gen_range(0..4)]).collect::<Vec<u8>>();
+        let sequences: Vec<(Vec<u8>, Vec<u8>)> = (0..64).map(|_| (generate_sequence(), generate_sequence())).collect();
+        let pairs: Vec<(&[u8], &[u8])> = sequences.iter().map(|(seq1, seq2)| (seq1.as_slice(), seq2.as_slice())).collect();
+        group.throughput(Throughput::Elements(pairs.len() as u64));
+
+        group.bench_with_input(BenchmarkId::new("scalar", len), &pairs, |b, pairs| {
+            b.iter(|| pairs.iter().map(|(seq1, seq2)| bitpal(seq1, seq2, &scoring)).collect::<Vec<i32>>())
+        });
+        group.bench_with_input(BenchmarkId::new("x4", len), &pairs, |b, pairs| b.iter(|| bitpal_batch(pairs, &scoring)));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_bitpal);
+criterion_main!(benches);
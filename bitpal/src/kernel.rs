@@ -0,0 +1,181 @@
+use std::cmp::max;
+use std::ops::{BitAnd, BitOr, BitXor, Not};
+
+use scoring::SimpleScoring;
+
+/// A word of bits the BitPAl kernel operates on: a single `u64`, or multiple `u64` lanes that are processed independently
+pub(crate) trait BitWord: Copy + BitAnd<Output = Self> + BitOr<Output = Self> + BitXor<Output = Self> + Not<Output = Self> {
+    const ZERO: Self;
+    const ONES: Self;
+
+    /// Add 2 words and the carry of the previous word (0 or 1), returning the sum and the carry for the next word
+    fn add_with_carry(self, other: Self, carry: Self) -> (Self, Self);
+
+    /// Shift 1 position to the left, shifting in the highest bit of the previous word
+    fn shift_in(self, previous_word: Self) -> Self;
+}
+
+impl BitWord for u64 {
+    const ZERO: u64 = 0;
+    const ONES: u64 = u64::MAX;
+
+    #[inline(always)]
+    fn add_with_carry(self, other: u64, carry: u64) -> (u64, u64) {
+        let sum = self.wrapping_add(other).wrapping_add(carry);
+        // the carry out of the highest bit, without branches so multiple lanes can be vectorised
+        (sum, ((self & other) | ((self | other) & !sum)) >> 63)
+    }
+
+    #[inline(always)]
+    fn shift_in(self, previous_word: u64) -> u64 {
+        (self << 1) | (previous_word >> 63)
+    }
+}
+
+/// The state of the BitPAl algorithm after processing a number of rows: the delta H classes of the last row
+pub(crate) struct Kernel<W> {
+    match_score: i32,
+    mismatch_score: i32,
+    gap_score: i32,
+    num_words: usize,
+    /// class c represents the difference `gap_score + c`
+    num_classes: usize,
+    /// the bits of the horizontal sequence in every word
+    all_ones: Vec<W>,
+    /// the delta H classes of every word
+    delta_h: Vec<W>,
+    /// delta_h_at_most[c]: delta H <= gap_score + c
+    delta_h_at_most: Vec<W>,
+    /// delta_v_at_least_shift[c]: delta V >= gap_score + c in the previous column, the highest class is always empty
+    delta_v_at_least_shift: Vec<W>,
+    new_delta_h: Vec<W>,
+    // the values of the previous word that are needed in the current word, for every class of delta V
+    previous_delta_v_at_least: Vec<W>,
+    previous_init: Vec<W>,
+    carries: Vec<W>,
+}
+
+impl<W: BitWord> Kernel<W> {
+    /// Initialize the kernel for the first row, where every delta H is the gap score
+    #[inline(always)]
+    pub(crate) fn new(scoring: &SimpleScoring, all_ones: Vec<W>) -> Kernel<W> {
+        let SimpleScoring { match_score, mismatch_score, gap_score, .. } = *scoring;
+        let num_words = all_ones.len();
+        let num_classes = (max(gap_score, max(match_score, mismatch_score) - gap_score) - gap_score + 1) as usize;
+
+        let mut delta_h = vec![W::ZERO; num_words * num_classes];
+        for word in 0..num_words {
+            delta_h[word * num_classes] = all_ones[word];
+        }
+
+        Kernel {
+            match_score,
+            mismatch_score,
+            gap_score,
+            num_words,
+            num_classes,
+            all_ones,
+            delta_h,
+            delta_h_at_most: vec![W::ZERO; num_classes],
+            delta_v_at_least_shift: vec![W::ZERO; num_classes + 1],
+            new_delta_h: vec![W::ZERO; num_classes],
+            previous_delta_v_at_least: vec![W::ZERO; num_classes],
+            previous_init: vec![W::ZERO; num_classes],
+            carries: vec![W::ZERO; num_classes],
+        }
+    }
+
+    pub(crate) fn num_words(&self) -> usize {
+        self.num_words
+    }
+
+    pub(crate) fn num_classes(&self) -> usize {
+        self.num_classes
+    }
+
+    /// The bits of the horizontal sequence where delta H equals `gap_score + class`
+    pub(crate) fn delta_h(&self, class: usize, word: usize) -> W {
+        self.delta_h[word * self.num_classes + class]
+    }
+
+    /// The delta H classes of every word of the current row, class `c` of word `w` is stored at `w * num_classes + c`
+    pub(crate) fn delta_h_row(&self) -> &[W] {
+        &self.delta_h
+    }
+
+    /// Calculate the delta H classes of the next row, where `match_vector(word)` marks the characters of the horizontal sequence
+    /// that match the character of this row
+    #[inline(always)]
+    pub(crate) fn next_row(&mut self, match_vector: impl Fn(usize) -> W) {
+        let Kernel { match_score, mismatch_score, gap_score, num_classes, .. } = *self;
+        // the first column always has a delta V of gap_score
+        self.previous_delta_v_at_least.fill(W::ZERO);
+        self.previous_init.fill(W::ZERO);
+        self.carries.fill(W::ZERO);
+
+        for word in 0..self.num_words {
+            let current_match_vector = match_vector(word);
+            let not_match = !current_match_vector;
+            let delta_h_word = &mut self.delta_h[word * num_classes..(word + 1) * num_classes];
+            let delta_h_at_most = &mut self.delta_h_at_most;
+            let delta_v_at_least_shift = &mut self.delta_v_at_least_shift;
+
+            let mut at_most = W::ZERO;
+            for class in 0..num_classes {
+                at_most = at_most | delta_h_word[class];
+                delta_h_at_most[class] = at_most;
+            }
+            let delta_h_at_most_value = |value: i32| match value - gap_score {
+                offset if offset < 0 => W::ZERO,
+                offset if offset as usize >= num_classes => W::ONES,
+                offset => delta_h_at_most[offset as usize],
+            };
+
+            // calculate delta V from high to low: delta V >= gap_score + class if
+            // - the substitution minus delta H is at least that high,
+            // - or delta V of the previous column is at least that high plus the amount delta H exceeds the gap score
+            delta_v_at_least_shift[0] = W::ONES;
+            for class in (1..num_classes).rev() {
+                let value = gap_score + class as i32;
+                let mut init = (current_match_vector & delta_h_at_most_value(match_score - value)) | (not_match & delta_h_at_most_value(mismatch_score - value));
+                for (h_class, &delta_h_class) in delta_h_word.iter().enumerate().take(num_classes - class).skip(1) {
+                    init = init | (delta_h_class & delta_v_at_least_shift[class + h_class]);
+                }
+
+                // if delta H is the gap score, delta V of the previous column propagates to the current one
+                let propagate = delta_h_word[0];
+                let init_shift = init.shift_in(self.previous_init[class]) & propagate;
+                let sum;
+                (sum, self.carries[class]) = init_shift.add_with_carry(propagate, self.carries[class]);
+                let delta_v_at_least = init | (((sum ^ propagate) | init_shift) & propagate);
+
+                delta_v_at_least_shift[class] = delta_v_at_least.shift_in(self.previous_delta_v_at_least[class]);
+                self.previous_init[class] = init;
+                self.previous_delta_v_at_least[class] = delta_v_at_least;
+            }
+
+            // calculate the new delta H from the shifted delta V, in the same way as delta V
+            let delta_v_at_most_value = |value: i32| match value - gap_score {
+                offset if offset < 0 => W::ZERO,
+                offset if offset as usize + 1 >= num_classes => W::ONES,
+                offset => !delta_v_at_least_shift[offset as usize + 1],
+            };
+            let mut previous_at_least = W::ONES;
+            for class in 1..=num_classes {
+                let at_least = if class == num_classes {
+                    W::ZERO
+                } else {
+                    let value = gap_score + class as i32;
+                    let mut at_least = (current_match_vector & delta_v_at_most_value(match_score - value)) | (not_match & delta_v_at_most_value(mismatch_score - value));
+                    for (h_class, &delta_h_class) in delta_h_word.iter().enumerate().skip(class) {
+                        at_least = at_least | (delta_h_class & !delta_v_at_least_shift[h_class - class + 1]);
+                    }
+                    at_least
+                };
+                self.new_delta_h[class - 1] = previous_at_least & !at_least & self.all_ones[word];
+                previous_at_least = at_least;
+            }
+            delta_h_word.copy_from_slice(&self.new_delta_h);
+        }
+    }
+}
@@ -1,93 +1,65 @@
+use std::cmp::max;
 use std::collections::{HashMap, HashSet};
 
-use crate::bitpal_errors::InputTooLongError;
-
-mod bitpal_errors;
-
-/// The BitPAl algorithm implemented for scoring: M = 1, I = -1, G = -3
-/// With the restriction that seq1 or seq2 needs to fit in 1 computer word (= seq1 or seq2 <= 64 characters)
-pub fn bitpal(seq1: &Vec<u8>, seq2: &Vec<u8>) -> Result<i32, InputTooLongError> {
-    // check validity of input
-    let (horizontal_seq, vertical_seq) = if seq1.len() <= 64 {
+use scoring::SimpleScoring;
+
+pub use crate::myers::{myers_banded_edit_distance, myers_edit_distance};
+pub use crate::simd::{bitpal_batch, bitpal_x4};
+pub use crate::traceback::{bitpal_align, BitpalAlignment};
+use crate::kernel::{BitWord, Kernel};
+
+mod kernel;
+mod myers;
+mod simd;
+mod traceback;
+
+/// The BitPAl algorithm for global alignment with any integer match, mismatch and gap score (the gap open score is ignored).
+/// Every difference between horizontally or vertically adjacent cells in the Needleman-Wunsch matrix lies between
+/// the gap score and the best substitution score minus the gap score. Every value in that range (a delta class) gets its own bitvector.
+/// The shortest sequence is placed horizontally and spans as many 64-bit words as needed,
+/// the carries of the additions and shifts are propagated from the lowest to the highest word.
+pub fn bitpal(seq1: &[u8], seq2: &[u8], scoring: &SimpleScoring) -> i32 {
+    // put the shortest sequence horizontally, so it needs as few words as possible
+    let (horizontal_seq, vertical_seq) = if seq1.len() <= seq2.len() {
         (seq1, seq2)
-    } else if seq2.len() <= 64 {
-        (seq2, seq1)
     } else {
-        return Err(InputTooLongError);
+        (seq2, seq1)
     };
+    let num_words = max(1, horizontal_seq.len().div_ceil(64));
 
     // create a set containing the used alphabet
     let mut alphabet: HashSet<u8> = HashSet::new();
     alphabet.extend(horizontal_seq.iter());
     alphabet.extend(vertical_seq.iter());
     // build the needed match vectors
-    let match_vectors = calculate_match_vectors(&horizontal_seq, &alphabet);
-
-    // vector containing horizontal_seq.len() 1's
-    let all_ones: u64 = 2_u64.wrapping_pow(horizontal_seq.len() as u32).wrapping_sub(1);
-    let mut delta_h_pos4: u64 = 0;
-    let mut delta_h_pos3: u64 = 0;
-    let mut delta_h_pos2: u64 = 0;
-    let mut delta_h_pos1: u64 = 0;
-    let mut delta_h_0: u64 = 0;
-    let mut delta_h_neg1: u64 = 0;
-    let mut delta_h_neg2: u64 = 0;
-    let mut delta_h_neg3: u64 = all_ones; // initialize first delta H with all 1's
+    let match_vectors = calculate_match_vectors(horizontal_seq, &alphabet);
 
+    // the words containing horizontal_seq.len() 1's
+    let mut kernel = Kernel::new(scoring, calculate_all_ones(horizontal_seq.len(), num_words));
     for &character in vertical_seq.iter() {
         let current_match_vector = match_vectors.get(&character).unwrap();
-        let not_match = !current_match_vector;
-        // calculate max value
-        let init_pos4 = current_match_vector & delta_h_neg3;
-        let delta_v_pos4_shift = ((init_pos4.wrapping_add(delta_h_neg3)) ^ delta_h_neg3) ^ init_pos4;
-
-        // calculate rest of delta v high from high to low
-        let remain_delta_h_neg3 = delta_h_neg3 ^ (delta_v_pos4_shift >> 1);
-        let delta_v_pos4_shift_or_match = delta_v_pos4_shift | current_match_vector;
-        let init_pos3s = delta_h_neg2 & delta_v_pos4_shift_or_match;
-        let delta_v_pos3_shift = ((init_pos3s << 1).wrapping_add(remain_delta_h_neg3)) ^ remain_delta_h_neg3;
-        let delta_v_pos3_shift_not_match = delta_v_pos3_shift & not_match;
-
-        // calculate min + 1 to mid
-        let delta_v_not_4_to_3_shift_or_match = !(delta_v_pos4_shift_or_match | delta_v_pos3_shift);
-        let delta_v_pos2_shift = ((delta_v_pos4_shift_or_match & delta_h_neg1) | (delta_v_pos3_shift_not_match & delta_h_neg2) | (delta_v_not_4_to_3_shift_or_match & delta_h_neg3)) << 1;
-        let delta_v_pos1_shift = ((delta_v_pos4_shift_or_match & delta_h_0) | (delta_v_pos3_shift_not_match & delta_h_neg1) | (delta_v_not_4_to_3_shift_or_match & delta_h_neg2)) << 1;
-        let delta_v_0_shift = ((delta_v_pos4_shift_or_match & delta_h_pos1) | (delta_v_pos3_shift_not_match & delta_h_0) | (delta_v_not_4_to_3_shift_or_match & delta_h_neg1)) << 1;
-        let delta_v_neg1_shift = ((delta_v_pos4_shift_or_match & delta_h_pos2) | (delta_v_pos3_shift_not_match & delta_h_pos1) | (delta_v_not_4_to_3_shift_or_match & delta_h_0)) << 1;
-        let delta_v_neg2_shift = ((delta_v_pos4_shift_or_match & delta_h_pos3) | (delta_v_pos3_shift_not_match & delta_h_pos2) | (delta_v_not_4_to_3_shift_or_match & delta_h_pos1)) << 1;
-
-        // fill in remaining min values
-        let delta_v_neg3_shift = all_ones ^ (delta_v_pos4_shift | delta_v_pos3_shift | delta_v_pos2_shift | delta_v_pos1_shift | delta_v_0_shift | delta_v_neg1_shift | delta_v_neg2_shift);
-
-        // prepare the delta H vectors for calculation
-        delta_h_pos4 |= current_match_vector; // add matches to the max vector
-        delta_h_pos2 = (delta_h_pos2 | delta_h_pos1 | delta_h_0 | delta_h_neg1 | delta_h_neg2 | delta_h_neg3) & not_match; // low to mid and remove match
-        delta_h_pos3 = delta_h_pos3 & not_match; // remove match
-
-        // calculate the new delta h vectors (from low to high since we use delta_h_pos2, delta_h_pos3 and delta_h_pos4 in the lowest values, so we can only modify them in the end)
-        delta_h_neg2 = (delta_h_pos4 & delta_v_pos3_shift) | (delta_h_pos3 & delta_v_pos2_shift) | (delta_h_pos2 & delta_v_pos1_shift);
-        delta_h_neg1 = (delta_h_pos4 & delta_v_pos2_shift) | (delta_h_pos3 & delta_v_pos1_shift) | (delta_h_pos2 & delta_v_0_shift);
-        delta_h_0 = (delta_h_pos4 & delta_v_pos1_shift) | (delta_h_pos3 & delta_v_0_shift) | (delta_h_pos2 & delta_v_neg1_shift);
-        delta_h_pos1 = (delta_h_pos4 & delta_v_0_shift) | (delta_h_pos3 & delta_v_neg1_shift) | (delta_h_pos2 & delta_v_neg2_shift);
-        delta_h_pos2 = (delta_h_pos4 & delta_v_neg1_shift) | (delta_h_pos3 & delta_v_neg2_shift) | (delta_h_pos2 & delta_v_neg3_shift);
-        delta_h_pos3 = (delta_h_pos4 & delta_v_neg2_shift) | (delta_h_pos3 & delta_v_neg3_shift);
-        delta_h_pos4 &= delta_v_neg3_shift;
-
-        // fill in remaining min values
-        delta_h_neg3 = all_ones ^ (delta_h_pos4 | delta_h_pos3 | delta_h_pos2 | delta_h_pos1 | delta_h_0 | delta_h_neg1 | delta_h_neg2);
+        kernel.next_row(|word| current_match_vector[word]);
     }
 
-    let score_mapping = HashMap::from([
-        (-3, delta_h_neg3),
-        (-2, delta_h_neg2),
-        (-1, delta_h_neg1),
-        (1, delta_h_pos1),
-        (2, delta_h_pos2),
-        (3, delta_h_pos3),
-        (4, delta_h_pos4)
-    ]);
-
-    Ok(gather_score(score_mapping, vertical_seq.len(), -3))
+    gather_kernel_score(&kernel, |word| word, vertical_seq.len(), scoring.gap_score)
+}
+
+/// Calculate the score from the delta H classes of the last row in the kernel, where `lane` selects the bits of one alignment from a word
+fn gather_kernel_score<W: BitWord>(kernel: &Kernel<W>, lane: impl Fn(W) -> u64, vertical_sequence_length: usize, gap_score: i32) -> i32 {
+    let score_mapping: HashMap<i32, Vec<u64>> = (0..kernel.num_classes())
+        .map(|class| (gap_score + class as i32, (0..kernel.num_words()).map(|word| lane(kernel.delta_h(class, word))).collect()))
+        .collect();
+
+    gather_score(score_mapping, vertical_sequence_length, gap_score)
+}
+
+/// The words containing `len` 1's
+fn calculate_all_ones(len: usize, num_words: usize) -> Vec<u64> {
+    (0..num_words).map(|word| match len.saturating_sub(word * 64) {
+        0 => 0,
+        ones if ones >= 64 => u64::MAX,
+        ones => (1 << ones) - 1,
+    }).collect()
 }
 
 #[allow(unused)]
@@ -101,14 +73,16 @@ fn print_binary(vector: u64) {
 }
 
 /// Calculate a hashmap with a bitvector representing the locations where a character occurs in seq.
-fn calculate_match_vectors(seq: &Vec<u8>, alphabet: &HashSet<u8>) -> HashMap<u8, u64> {
-    let mut match_vectors: HashMap<u8, u64> = HashMap::new();
+/// Every bitvector consists of enough words to contain seq, with at least 1 word.
+fn calculate_match_vectors(seq: &[u8], alphabet: &HashSet<u8>) -> HashMap<u8, Vec<u64>> {
+    let num_words = max(1, seq.len().div_ceil(64));
+    let mut match_vectors: HashMap<u8, Vec<u64>> = HashMap::new();
     for &letter in alphabet {
-        match_vectors.insert(letter, 0);
+        match_vectors.insert(letter, vec![0; num_words]);
     }
 
     for (i, &character) in seq.iter().enumerate() {
-        match_vectors.entry(character).and_modify(|vector| *vector |= 1 << i);
+        match_vectors.entry(character).and_modify(|vector| vector[i / 64] |= 1 << (i % 64));
     }
 
     match_vectors
@@ -116,10 +90,10 @@ fn calculate_match_vectors(seq: &Vec<u8>, alphabet: &HashSet<u8>) -> HashMap<u8,
 
 /// Calculate the score using the bitvectors provided in `delta_h_map`.
 /// The key is the value i, that the bits represent, while the value is the bitvector
-fn gather_score(delta_h_map: HashMap<i32, u64>, vertical_sequence_length: usize, gap_score: i32) -> i32 {
+fn gather_score(delta_h_map: HashMap<i32, Vec<u64>>, vertical_sequence_length: usize, gap_score: i32) -> i32 {
     let mut score = gap_score * vertical_sequence_length as i32;
     for (value, bitrow) in delta_h_map {
-        score += bitrow.count_ones() as i32 * value;
+        score += bitrow.iter().map(|word| word.count_ones()).sum::<u32>() as i32 * value;
     }
     score
 }
@@ -131,13 +105,12 @@ mod tests {
     use std::ops::Range;
 
     use rand::Rng;
-    use rand::rngs::ThreadRng;
 
     use fasta_reader::read_fasta;
     use needleman_wunsch::construct_matrix;
+    use scoring::SimpleScoring;
 
     use crate::{bitpal, calculate_match_vectors, gather_score};
-    use crate::bitpal_errors::InputTooLongError;
 
     #[test]
     fn test_match_vector() {
@@ -145,10 +118,24 @@ mod tests {
         let alphabet = HashSet::from([b'A', b'C', b'G', b'T']);
         let res = calculate_match_vectors(&seq.into_bytes(), &alphabet);
         let expected = HashMap::from([
-            (b'A', 69),
-            (b'C', 10),
-            (b'G', 16),
-            (b'T', 32),
+            (b'A', vec![69]),
+            (b'C', vec![10]),
+            (b'G', vec![16]),
+            (b'T', vec![32]),
+        ]);
+        assert_eq!(res, expected);
+    }
+
+    #[test]
+    fn test_match_vector_multiple_words() {
+        let mut seq = vec![b'A'; 64];
+        seq.extend(b"CA");
+        let alphabet = HashSet::from([b'A', b'C', b'G']);
+        let res = calculate_match_vectors(&seq, &alphabet);
+        let expected = HashMap::from([
+            (b'A', vec![u64::MAX, 2]),
+            (b'C', vec![0, 1]),
+            (b'G', vec![0, 0]),
         ]);
         assert_eq!(res, expected);
     }
@@ -156,18 +143,18 @@ mod tests {
     #[test]
     fn test_gather_score() {
         let last_row_res = HashMap::from([
-            (-3, 4),
-            (-2, 6),
-            (-1, 0),
-            (1, 0),
-            (2, 10),
-            (3, 69),
-            (4, 3)
+            (-3, vec![4]),
+            (-2, vec![6]),
+            (-1, vec![0]),
+            (1, vec![0]),
+            (2, vec![10]),
+            (3, vec![69]),
+            (4, vec![3])
         ]);
 
         let res = gather_score(last_row_res, 5, -3);
 
-        let expected = 5 * -3 + (1 * -3 + 2 * -2 + 2 * 2 + 3 * 3 + 2 * 4);
+        let expected = 5 * -3 + (-3 + 2 * -2 + 2 * 2 + 3 * 3 + 2 * 4);
         assert_eq!(res, expected);
     }
 
@@ -177,9 +164,9 @@ mod tests {
         let seq1_chars = seq1.into_bytes();
         let seq2_chars = seq2.into_bytes();
 
-        let matrix = construct_matrix(&seq1_chars, &seq2_chars, 1, -1, -3);
-        let nw_score = matrix[seq2_chars.len()][seq1_chars.len()];
-        let bitpal = bitpal(&seq1_chars, &seq2_chars)?;
+        let matrix = construct_matrix(&seq1_chars, &seq2_chars, &SimpleScoring::new(1, -1, -3));
+        let nw_score = matrix[(seq2_chars.len(), seq1_chars.len())];
+        let bitpal = bitpal(&seq1_chars, &seq2_chars, &SimpleScoring::new(1, -1, -3));
 
         assert_eq!(bitpal, nw_score);
 
@@ -190,17 +177,18 @@ mod tests {
     fn test_bitpal_minimal() -> Result<(), Box<dyn Error>> {
         let seq1 = "A".to_string().into_bytes();
         let seq2 = "TG".to_string().into_bytes();
-        let matrix = construct_matrix(&seq1, &seq2, 1, -1, -3);
-        let nw_score = matrix[seq2.len()][seq1.len()];
-        let bitpal = bitpal(&seq1, &seq2)?;
+        let matrix = construct_matrix(&seq1, &seq2, &SimpleScoring::new(1, -1, -3));
+        let nw_score = matrix[(seq2.len(), seq1.len())];
+        let bitpal = bitpal(&seq1, &seq2, &SimpleScoring::new(1, -1, -3));
 
         assert_eq!(bitpal, nw_score);
 
         Ok(())
     }
 
-    /// Helper function to fuzz test BitPAl implementation where a random sequence with a length in the size_range is generated using the provided alphabet
-    fn generate_sequence_with_alphabet(alphabet: &Vec<u8>, rng: &mut ThreadRng, size_range: Range<usize>) -> Vec<u8> {
+    /// Helper function to fuzz test BitPAl implementation where a random sequence with a length in the size_range is generated using the provided alphabet.
+    /// Also used by the tests of the other modules.
+    pub(crate) fn generate_sequence_with_alphabet(alphabet: &[u8], rng: &mut impl Rng, size_range: Range<usize>) -> Vec<u8> {
         let seq_len = rng.gen_range(size_range);
         let mut seq = vec![];
         for _ in 0..seq_len {
@@ -215,11 +203,11 @@ mod tests {
         let mut rng = rand::thread_rng();
         let valid_letters = vec![b'A', b'C', b'G', b'T'];
         for _ in 0..5000 {
-            let seq1 = generate_sequence_with_alphabet(&valid_letters, &mut rng, 1..65); // this one is set horizontal, so make sure it is not longer than 1 word
+            let seq1 = generate_sequence_with_alphabet(&valid_letters, &mut rng, 1..65);
             let seq2 = generate_sequence_with_alphabet(&valid_letters, &mut rng, 1..513);
-            let matrix = construct_matrix(&seq1, &seq2, 1, -1, -3);
-            let nw_score = matrix[seq2.len()][seq1.len()];
-            let bitpal = bitpal(&seq1, &seq2)?;
+            let matrix = construct_matrix(&seq1, &seq2, &SimpleScoring::new(1, -1, -3));
+            let nw_score = matrix[(seq2.len(), seq1.len())];
+            let bitpal = bitpal(&seq1, &seq2, &SimpleScoring::new(1, -1, -3));
 
             assert_eq!(bitpal, nw_score);
         }
@@ -228,13 +216,58 @@ mod tests {
     }
 
     #[test]
-    fn test_bitpal_size_check() -> Result<(), Box<dyn Error>> {
+    fn test_bitpal_scoring_fuzzing() -> Result<(), Box<dyn Error>> {
         let mut rng = rand::thread_rng();
         let valid_letters = vec![b'A', b'C', b'G', b'T'];
-        // make both sequences larger than 65
-        let seq1 = generate_sequence_with_alphabet(&valid_letters, &mut rng, 65..66);
-        let seq2 = generate_sequence_with_alphabet(&valid_letters, &mut rng, 65..66);
-        assert!(bitpal(&seq1, &seq2).is_err_and(|e| e == InputTooLongError));
+        let scoring_schemes = [
+            SimpleScoring::new(2, -1, -2),
+            SimpleScoring::new(0, -1, -1),
+            SimpleScoring::new(5, -4, -10),
+            SimpleScoring::new(1, -7, -2),
+            SimpleScoring::new(3, 1, -1),
+            SimpleScoring::new(-1, -2, -4),
+            SimpleScoring::new(1, -1, 0),
+            SimpleScoring::new(1, -1, 2),
+        ];
+        for scoring in scoring_schemes {
+            for _ in 0..200 {
+                let seq1 = generate_sequence_with_alphabet(&valid_letters, &mut rng, 0..150);
+                let seq2 = generate_sequence_with_alphabet(&valid_letters, &mut rng, 0..300);
+                let matrix = construct_matrix(&seq1, &seq2, &scoring);
+                let nw_score = matrix[(seq2.len(), seq1.len())];
+                assert_eq!(bitpal(&seq1, &seq2, &scoring), nw_score, "{:?}", scoring);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bitpal_multiple_words() -> Result<(), Box<dyn Error>> {
+        let mut rng = rand::thread_rng();
+        let valid_letters = vec![b'A', b'C', b'G', b'T'];
+        // both sequences are longer than 1 word, including lengths that are exact multiples of the word size
+        for size_range in [65..66, 128..129, 129..130, 65..1025] {
+            for _ in 0..20 {
+                let seq1 = generate_sequence_with_alphabet(&valid_letters, &mut rng, size_range.clone());
+                let seq2 = generate_sequence_with_alphabet(&valid_letters, &mut rng, size_range.clone());
+                let matrix = construct_matrix(&seq1, &seq2, &SimpleScoring::new(1, -1, -3));
+                let nw_score = matrix[(seq2.len(), seq1.len())];
+                assert_eq!(bitpal(&seq1, &seq2, &SimpleScoring::new(1, -1, -3)), nw_score);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bitpal_verylarge() -> Result<(), Box<dyn Error>> {
+        let (seq1, seq2) = read_fasta("../tests/verylarge.fasta")?;
+        let seq1_chars = seq1.into_bytes();
+        let seq2_chars = seq2.into_bytes();
+
+        let matrix = construct_matrix(&seq1_chars, &seq2_chars, &SimpleScoring::new(1, -1, -3));
+        assert_eq!(bitpal(&seq1_chars, &seq2_chars, &SimpleScoring::new(1, -1, -3)), matrix[(seq2_chars.len(), seq1_chars.len())]);
 
         Ok(())
     }
@@ -246,9 +279,9 @@ mod tests {
         // make seq2 the sequence that should be horizontal
         let seq1 = generate_sequence_with_alphabet(&valid_letters, &mut rng, 65..66);
         let seq2 = generate_sequence_with_alphabet(&valid_letters, &mut rng, 1..65);
-        let matrix = construct_matrix(&seq1, &seq2, 1, -1, -3);
-        let nw_score = matrix[seq2.len()][seq1.len()];
-        let bitpal = bitpal(&seq1, &seq2)?;
+        let matrix = construct_matrix(&seq1, &seq2, &SimpleScoring::new(1, -1, -3));
+        let nw_score = matrix[(seq2.len(), seq1.len())];
+        let bitpal = bitpal(&seq1, &seq2, &SimpleScoring::new(1, -1, -3));
         assert_eq!(bitpal, nw_score);
 
         Ok(())
@@ -261,7 +294,7 @@ mod tests {
         // make seq2 the sequence that should be horizontal
         let seq1 = vec![];
         let seq2 = generate_sequence_with_alphabet(&valid_letters, &mut rng, 1..65);
-        let bitpal = bitpal(&seq1, &seq2)?;
+        let bitpal = bitpal(&seq1, &seq2, &SimpleScoring::new(1, -1, -3));
         assert_eq!(bitpal, -3 * seq2.len() as i32);
 
         Ok(())
@@ -274,7 +307,7 @@ mod tests {
         // make seq2 the sequence that should be horizontal
         let seq1 = generate_sequence_with_alphabet(&valid_letters, &mut rng, 1..65);
         let seq2 = vec![];
-        let bitpal = bitpal(&seq1, &seq2)?;
+        let bitpal = bitpal(&seq1, &seq2, &SimpleScoring::new(1, -1, -3));
         assert_eq!(bitpal, -3 * seq1.len() as i32);
 
         Ok(())
@@ -285,7 +318,7 @@ mod tests {
         // make seq2 the sequence that should be horizontal
         let seq1 = vec![];
         let seq2 = vec![];
-        let bitpal = bitpal(&seq1, &seq2)?;
+        let bitpal = bitpal(&seq1, &seq2, &SimpleScoring::new(1, -1, -3));
         assert_eq!(bitpal, 0);
 
         Ok(())
@@ -298,9 +331,9 @@ mod tests {
         let valid_letters = vec![b'A', b'C', b'G', b'T'];
         let seq1 = generate_sequence_with_alphabet(&valid_letters, &mut rng, 64..65);
         let seq2 = generate_sequence_with_alphabet(&valid_letters, &mut rng, 64..65);
-        let matrix = construct_matrix(&seq1, &seq2, 1, -1, -3);
-        let nw_score = matrix[seq2.len()][seq1.len()];
-        let bitpal = bitpal(&seq1, &seq2)?;
+        let matrix = construct_matrix(&seq1, &seq2, &SimpleScoring::new(1, -1, -3));
+        let nw_score = matrix[(seq2.len(), seq1.len())];
+        let bitpal = bitpal(&seq1, &seq2, &SimpleScoring::new(1, -1, -3));
         assert_eq!(bitpal, nw_score);
 
         Ok(())
@@ -1,27 +1,61 @@
+use std::error::Error;
+use std::str::from_utf8;
+
 use clap::Parser;
 
-use bitpal::bitpal;
-use fasta_reader::read_fasta;
+use bitpal::{bitpal, bitpal_align};
+use fasta_reader::{read_fasta, read_fasta_regions};
+use scoring::SimpleScoring;
 
 #[derive(Parser, Debug)]
 #[clap(allow_negative_numbers = true)]
 struct Args {
-    /// The filename of the input fasta file containing the 2 sequences
+    /// The filename of the input FASTA or FASTQ file containing the 2 sequences
     #[clap(short, long)]
     filename: String,
+    /// Fetch the 2 sequences to align from the indexed input file instead of using the first 2 records.
+    /// Each region is written as `name`, `name:start` or `name:start-end` (1-based, inclusive), e.g. `--region chr1:1000-2000 read1`
+    #[clap(short, long, num_args = 2, value_names = ["REGION1", "REGION2"])]
+    region: Option<Vec<String>>,
+    /// The score used when there is a match
+    #[clap(short, long, default_value_t = 1)]
+    match_score: i32,
+    /// The score used when there is a mismatch
+    #[clap(short = 'i', long, default_value_t = - 1)]
+    mismatch_score: i32,
+    /// The score used when there is a gap
+    #[clap(short, long, default_value_t = - 3)]
+    gap_score: i32,
+    /// Also print an optimal alignment, which needs to store the delta vectors of every row
+    #[clap(short, long)]
+    alignment: bool,
 }
 
-fn main() {
+fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
-    let Args { filename } = args;
-    let (seq1, seq2) = read_fasta(&filename).expect("Failed to read the input fasta file");
+    let Args { filename, region, match_score, mismatch_score, gap_score, alignment } = args;
+    let (seq1, seq2) = match region {
+        Some(regions) => read_fasta_regions(&filename, &regions[0], &regions[1])?,
+        None => read_fasta(&filename)?,
+    };
     let seq1_chars = seq1.into_bytes();
     let seq2_chars = seq2.into_bytes();
 
-    let result_score = bitpal(&seq1_chars, &seq2_chars);
+    let scoring = SimpleScoring::new(match_score, mismatch_score, gap_score);
 
-    match result_score {
-        Ok(score) => println!("The resulting global alignment score is: {}", score),
-        Err(error) => eprintln!("{}", error)
+    if !alignment {
+        let score = bitpal(&seq1_chars, &seq2_chars, &scoring);
+        println!("The resulting global alignment score is: {}", score);
+        return Ok(());
     }
+
+    let res = bitpal_align(&seq1_chars, &seq2_chars, &scoring);
+    let (aligned_seq1, diff_line, aligned_seq2) = res.alignment;
+    println!("The resulting global alignment score is: {}", res.score);
+    println!();
+    println!("Aligned sequences:");
+    println!("{}", from_utf8(&aligned_seq1)?);
+    println!("{}", from_utf8(&diff_line)?);
+    println!("{}", from_utf8(&aligned_seq2)?);
+    Ok(())
 }
@@ -0,0 +1,252 @@
+use std::cmp::max;
+use std::collections::{HashMap, HashSet};
+
+use crate::calculate_match_vectors;
+
+/// The vertical deltas of a block of 64 cells in a column: `positive` marks the cells that are 1 higher than the cell above,
+/// `negative` the cells that are 1 lower. All other cells equal the cell above.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Block {
+    positive: u64,
+    negative: u64,
+}
+
+impl Block {
+    /// Calculate the block in the next column, given the cells that match the character of that column and the horizontal delta
+    /// (-1, 0 or 1) of the cell above the block.
+    /// Returns the horizontal deltas of the cells in the block: (positive, negative)
+    #[inline(always)]
+    fn advance(&mut self, match_vector: u64, h_in: i32) -> (u64, u64) {
+        let Block { positive, negative } = *self;
+        let x_v = match_vector | negative;
+        let match_vector = if h_in < 0 { match_vector | 1 } else { match_vector };
+        let x_h = ((match_vector & positive).wrapping_add(positive) ^ positive) | match_vector;
+
+        let positive_h = negative | !(x_h | positive);
+        let negative_h = positive & x_h;
+
+        let positive_h_shift = (positive_h << 1) | (h_in > 0) as u64;
+        let negative_h_shift = (negative_h << 1) | (h_in < 0) as u64;
+        self.positive = negative_h_shift | !(x_v | positive_h_shift);
+        self.negative = positive_h_shift & x_v;
+        (positive_h, negative_h)
+    }
+}
+
+/// The delta (-1, 0 or 1) that is stored in the given bit of a pair of positive and negative bitvectors
+#[inline(always)]
+fn delta_at((positive, negative): (u64, u64), bit: usize) -> i32 {
+    ((positive >> bit) & 1) as i32 - ((negative >> bit) & 1) as i32
+}
+
+/// The match vectors of vertical_seq, for every character that occurs in one of both sequences
+fn alphabet_match_vectors(vertical_seq: &[u8], horizontal_seq: &[u8]) -> HashMap<u8, Vec<u64>> {
+    let mut alphabet: HashSet<u8> = HashSet::new();
+    alphabet.extend(vertical_seq.iter());
+    alphabet.extend(horizontal_seq.iter());
+    calculate_match_vectors(vertical_seq, &alphabet)
+}
+
+/// Calculate the edit distance (Levenshtein distance) of seq1 and seq2 with Myers' bit-vector algorithm (1999).
+/// The shortest sequence is placed vertically and spans as many 64-bit blocks as needed,
+/// the horizontal delta at the bottom of a block is passed on to the block below.
+pub fn myers_edit_distance(seq1: &[u8], seq2: &[u8]) -> usize {
+    // put the shortest sequence vertically, so it needs as few blocks as possible
+    let (vertical_seq, horizontal_seq) = if seq1.len() <= seq2.len() { (seq1, seq2) } else { (seq2, seq1) };
+    if vertical_seq.is_empty() {
+        return horizontal_seq.len();
+    }
+
+    let match_vectors = alphabet_match_vectors(vertical_seq, horizontal_seq);
+    let num_blocks = vertical_seq.len().div_ceil(64);
+    let last_bit = (vertical_seq.len() - 1) % 64;
+
+    // the first column increases by 1 in every cell
+    let mut blocks = vec![Block { positive: u64::MAX, negative: 0 }; num_blocks];
+    let mut distance = vertical_seq.len() as i32;
+    for character in horizontal_seq {
+        let match_vector = &match_vectors[character];
+        // the first row increases by 1 in every cell
+        let mut h_in = 1;
+        for (block_index, block) in blocks.iter_mut().enumerate() {
+            let delta_h = block.advance(match_vector[block_index], h_in);
+            if block_index + 1 == num_blocks {
+                // the cells below the last character of vertical_seq don't exist
+                distance += delta_at(delta_h, last_bit);
+            } else {
+                h_in = delta_at(delta_h, 63);
+            }
+        }
+    }
+    distance as usize
+}
+
+/// The 64 bits of `vector` starting at bit `start`, where the bits before the start and after the end of the vector are 0
+fn bits_from(vector: &[u64], start: isize) -> u64 {
+    if start <= -64 {
+        return 0;
+    }
+    if start < 0 {
+        return vector.first().map_or(0, |&word| word << -start);
+    }
+    let (word, shift) = (start as usize / 64, start as usize % 64);
+    let low = vector.get(word).map_or(0, |&word| word >> shift);
+    let high = if shift == 0 { 0 } else { vector.get(word + 1).map_or(0, |&word| word << (64 - shift)) };
+    low | high
+}
+
+/// Calculate the edit distance of seq1 and seq2 if it is at most `max_distance`, with Hyyrö's banded variant of Myers' algorithm.
+/// Only the `2 * max_distance + 1` diagonals around the main diagonal are calculated, using O(n * max_distance / 64) time.
+/// Bit b of the column of character j of seq1 holds row `j - max_distance + b` of seq2, so every bit stays on the same diagonal:
+/// the blocks are shifted 1 bit to the right before calculating the next column.
+/// Returns `None` if the edit distance is larger than `max_distance`.
+pub fn myers_banded_edit_distance(seq1: &[u8], seq2: &[u8], max_distance: usize) -> Option<usize> {
+    if seq1.len().abs_diff(seq2.len()) > max_distance {
+        return None;
+    }
+    // the edit distance is at most the length of the longest sequence, so a larger maximum only widens the band
+    let max_distance = max_distance.min(max(seq1.len(), seq2.len()));
+
+    let match_vectors = alphabet_match_vectors(seq2, seq1);
+    let band_len = 2 * max_distance + 1;
+    let num_blocks = band_len.div_ceil(64);
+    let (bottom_block, bottom_bit) = ((band_len - 1) / 64, (band_len - 1) % 64);
+
+    // the bits of the first columns can lie above the first row, those rows are extended so the first column decreases by 1
+    // up to the first row, which keeps the first row increasing by 1 in every cell
+    let mut blocks: Vec<Block> = (0..num_blocks).map(|block_index| {
+        let rows_up_to_first = (max_distance + 1).saturating_sub(64 * block_index).min(64);
+        let negative = if rows_up_to_first == 64 { u64::MAX } else { (1 << rows_up_to_first) - 1 };
+        Block { positive: !negative, negative }
+    }).collect();
+    // the value of the top cell of the band
+    let mut top_distance = i32::try_from(max_distance).expect("The edit distance fits in an i32");
+
+    for (col, character) in seq1.iter().enumerate() {
+        // shift every bit to the diagonal of the next column, the vertical delta of the new bottom cell is 1
+        for block_index in 0..num_blocks {
+            let next = blocks.get(block_index + 1).copied().unwrap_or(Block { positive: 0, negative: 0 });
+            let block = &mut blocks[block_index];
+            block.positive = (block.positive >> 1) | (next.positive << 63);
+            block.negative = (block.negative >> 1) | (next.negative << 63);
+        }
+        blocks[bottom_block].positive |= 1 << bottom_bit;
+        blocks[bottom_block].negative &= !(1 << bottom_bit);
+        let top_delta_v = delta_at((blocks[0].positive, blocks[0].negative), 0);
+
+        // the cell above the band is at least 1 higher than the cell to its left
+        let mut h_in = 1;
+        let match_vector = &match_vectors[character];
+        let first_row = col as isize + 1 - max_distance as isize;
+        for (block_index, block) in blocks.iter_mut().enumerate() {
+            // bit b matches character `row - 1` of seq2
+            let delta_h = block.advance(bits_from(match_vector, first_row - 1 + 64 * block_index as isize), h_in);
+            if block_index == 0 {
+                top_distance += top_delta_v + delta_at(delta_h, 0);
+            }
+            h_in = delta_at(delta_h, 63);
+        }
+    }
+
+    // add the vertical deltas from the top cell of the band down to the bottom right corner
+    let corner_bit = seq2.len() + max_distance - seq1.len();
+    let mut distance = top_distance;
+    for (block_index, block) in blocks.iter().enumerate() {
+        let mask = match (corner_bit + 1).saturating_sub(64 * block_index) {
+            0 => 0,
+            bits if bits >= 64 => u64::MAX,
+            bits => (1 << bits) - 1,
+        } & if block_index == 0 { !1 } else { u64::MAX };
+        distance += (block.positive & mask).count_ones() as i32 - (block.negative & mask).count_ones() as i32;
+    }
+
+    Some(distance as usize).filter(|&distance| distance <= max_distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use rand::Rng;
+
+    use fasta_reader::read_fasta;
+    use needleman_wunsch::construct_matrix;
+    use scoring::SimpleScoring;
+
+    use crate::{myers_banded_edit_distance, myers_edit_distance};
+    use crate::tests::generate_sequence_with_alphabet;
+
+    /// The edit distance calculated with Needleman-Wunsch
+    fn nw_edit_distance(seq1: &[u8], seq2: &[u8]) -> usize {
+        let matrix = construct_matrix(seq1, seq2, &SimpleScoring::new(0, -1, -1));
+        -matrix[(seq2.len(), seq1.len())] as usize
+    }
+
+    #[test]
+    fn test_myers_edit_distance() -> Result<(), Box<dyn Error>> {
+        assert_eq!(myers_edit_distance(b"kitten", b"sitting"), 3);
+        assert_eq!(myers_edit_distance(b"", b"ACG"), 3);
+        assert_eq!(myers_edit_distance(b"ACG", b""), 3);
+        assert_eq!(myers_edit_distance(b"", b""), 0);
+
+        let (seq1, seq2) = read_fasta("../tests/dna_input.fasta")?;
+        let seq1_chars = seq1.into_bytes();
+        let seq2_chars = seq2.into_bytes();
+        assert_eq!(myers_edit_distance(&seq1_chars, &seq2_chars), nw_edit_distance(&seq1_chars, &seq2_chars));
+        Ok(())
+    }
+
+    #[test]
+    fn test_myers_edit_distance_fuzzing() {
+        let mut rng = rand::thread_rng();
+        // both sequences can span multiple blocks, including lengths that are exact multiples of the block size
+        for max_len in [10, 65, 129, 400] {
+            for _ in 0..200 {
+                let seq1 = generate_sequence_with_alphabet(b"ACGT", &mut rng, 0..max_len);
+                let seq2 = generate_sequence_with_alphabet(b"ACGT", &mut rng, 0..max_len);
+                assert_eq!(myers_edit_distance(&seq1, &seq2), nw_edit_distance(&seq1, &seq2), "{:?} {:?}", seq1, seq2);
+            }
+        }
+        // a prefix that ends exactly at the end of a block
+        let seq: Vec<u8> = (0..192).map(|_| b"ACGT"[rng.gen_range(0..4)]).collect();
+        assert_eq!(myers_edit_distance(&seq[..128], &seq), 64);
+    }
+
+    #[test]
+    fn test_myers_banded_edit_distance() {
+        assert_eq!(myers_banded_edit_distance(b"kitten", b"sitting", 3), Some(3));
+        assert_eq!(myers_banded_edit_distance(b"kitten", b"sitting", 2), None);
+        assert_eq!(myers_banded_edit_distance(b"", b"ACG", 3), Some(3));
+        assert_eq!(myers_banded_edit_distance(b"", b"ACG", 2), None);
+        assert_eq!(myers_banded_edit_distance(b"", b"", 0), Some(0));
+        assert_eq!(myers_banded_edit_distance(b"ACGT", b"ACGT", 0), Some(0));
+        assert_eq!(myers_banded_edit_distance(b"kitten", b"sitting", usize::MAX), Some(3));
+    }
+
+    #[test]
+    fn test_myers_banded_edit_distance_fuzzing() {
+        let mut rng = rand::thread_rng();
+        for max_len in [10, 100, 300] {
+            for _ in 0..200 {
+                let seq1 = generate_sequence_with_alphabet(b"ACGT", &mut rng, 0..max_len);
+                // similar sequences, so the edit distance is often inside small bands
+                let mut seq2 = seq1.clone();
+                for _ in 0..rng.gen_range(0..max_len / 4 + 1) {
+                    let position = rng.gen_range(0..=seq2.len());
+                    match rng.gen_range(0..3) {
+                        0 => seq2.insert(position, b"ACGT"[rng.gen_range(0..4)]),
+                        1 if position < seq2.len() => { seq2.remove(position); }
+                        _ if position < seq2.len() => seq2[position] = b"ACGT"[rng.gen_range(0..4)],
+                        _ => {}
+                    }
+                }
+                let distance = nw_edit_distance(&seq1, &seq2);
+                // bands that span multiple blocks are included
+                for max_distance in [0, 1, 5, distance.saturating_sub(1), distance, distance + 1, 40, 100, usize::MAX] {
+                    let expected = Some(distance).filter(|&distance| distance <= max_distance);
+                    assert_eq!(myers_banded_edit_distance(&seq1, &seq2, max_distance), expected, "{:?} {:?} {}", seq1, seq2, max_distance);
+                }
+            }
+        }
+    }
+}
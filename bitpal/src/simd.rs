@@ -0,0 +1,184 @@
+use std::cmp::max;
+use std::collections::{HashMap, HashSet};
+use std::ops::{BitAnd, BitOr, BitXor, Not};
+
+use scoring::SimpleScoring;
+
+use crate::kernel::{BitWord, Kernel};
+use crate::{calculate_all_ones, calculate_match_vectors, gather_kernel_score};
+
+/// The number of alignments that are calculated at the same time
+const LANES: usize = 4;
+
+/// 4 independent 64-bit words, one for every alignment.
+/// All operations work lane by lane, so they are compiled to 256-bit SIMD instructions when AVX2 is enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Lanes([u64; LANES]);
+
+impl Lanes {
+    #[inline(always)]
+    fn zip(self, other: Lanes, f: impl Fn(u64, u64) -> u64) -> Lanes {
+        Lanes(std::array::from_fn(|lane| f(self.0[lane], other.0[lane])))
+    }
+}
+
+impl BitAnd for Lanes {
+    type Output = Lanes;
+
+    #[inline(always)]
+    fn bitand(self, other: Lanes) -> Lanes {
+        self.zip(other, |a, b| a & b)
+    }
+}
+
+impl BitOr for Lanes {
+    type Output = Lanes;
+
+    #[inline(always)]
+    fn bitor(self, other: Lanes) -> Lanes {
+        self.zip(other, |a, b| a | b)
+    }
+}
+
+impl BitXor for Lanes {
+    type Output = Lanes;
+
+    #[inline(always)]
+    fn bitxor(self, other: Lanes) -> Lanes {
+        self.zip(other, |a, b| a ^ b)
+    }
+}
+
+impl Not for Lanes {
+    type Output = Lanes;
+
+    #[inline(always)]
+    fn not(self) -> Lanes {
+        Lanes(self.0.map(|a| !a))
+    }
+}
+
+impl BitWord for Lanes {
+    const ZERO: Lanes = Lanes([0; LANES]);
+    const ONES: Lanes = Lanes([u64::MAX; LANES]);
+
+    #[inline(always)]
+    fn add_with_carry(self, other: Lanes, carry: Lanes) -> (Lanes, Lanes) {
+        let sum = self.zip(other, u64::wrapping_add).zip(carry, u64::wrapping_add);
+        let carry = Lanes(std::array::from_fn(|lane| {
+            let (a, b, sum) = (self.0[lane], other.0[lane], sum.0[lane]);
+            ((a & b) | ((a | b) & !sum)) >> 63
+        }));
+        (sum, carry)
+    }
+
+    #[inline(always)]
+    fn shift_in(self, previous_word: Lanes) -> Lanes {
+        self.zip(previous_word, |word, previous_word| (word << 1) | (previous_word >> 63))
+    }
+}
+
+/// Calculate the BitPAl score of 4 independent pairs of sequences at the same time, using 256-bit SIMD instructions if the CPU supports AVX2.
+/// The results are the same as calling `bitpal` on every pair.
+pub fn bitpal_x4(pairs: [(&[u8], &[u8]); LANES], scoring: &SimpleScoring) -> [i32; LANES] {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        // SAFETY: the CPU supports AVX2
+        return unsafe { bitpal_x4_avx2(pairs, scoring) };
+    }
+    bitpal_lanes(pairs, scoring)
+}
+
+/// Calculate the BitPAl score of every pair of sequences, 4 pairs at a time
+pub fn bitpal_batch(pairs: &[(&[u8], &[u8])], scoring: &SimpleScoring) -> Vec<i32> {
+    let mut scores = Vec::with_capacity(pairs.len());
+    for chunk in pairs.chunks(LANES) {
+        // fill the unused lanes of the last chunk with empty sequences
+        let lanes: [(&[u8], &[u8]); LANES] = std::array::from_fn(|lane| chunk.get(lane).copied().unwrap_or((&[], &[])));
+        scores.extend_from_slice(&bitpal_x4(lanes, scoring)[..chunk.len()]);
+    }
+    scores
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn bitpal_x4_avx2(pairs: [(&[u8], &[u8]); LANES], scoring: &SimpleScoring) -> [i32; LANES] {
+    bitpal_lanes(pairs, scoring)
+}
+
+/// BitPAl for every pair in its own lane, inlined in `bitpal_x4_avx2` so it is compiled with AVX2 enabled
+#[inline(always)]
+fn bitpal_lanes(pairs: [(&[u8], &[u8]); LANES], scoring: &SimpleScoring) -> [i32; LANES] {
+    // put the shortest sequence of every pair horizontally
+    let pairs = pairs.map(|(seq1, seq2)| if seq1.len() <= seq2.len() { (seq1, seq2) } else { (seq2, seq1) });
+    let num_words = pairs.iter().map(|(horizontal_seq, _)| max(1, horizontal_seq.len().div_ceil(64))).max().unwrap_or(1);
+    let num_rows = pairs.iter().map(|(_, vertical_seq)| vertical_seq.len()).max().unwrap_or(0);
+
+    let match_vectors: [HashMap<u8, Vec<u64>>; LANES] = pairs.map(|(horizontal_seq, vertical_seq)| {
+        let mut alphabet: HashSet<u8> = HashSet::new();
+        alphabet.extend(horizontal_seq.iter());
+        alphabet.extend(vertical_seq.iter());
+        calculate_match_vectors(horizontal_seq, &alphabet)
+    });
+    let all_ones: [Vec<u64>; LANES] = pairs.map(|(horizontal_seq, _)| calculate_all_ones(horizontal_seq.len(), num_words));
+    let mut kernel = Kernel::new(scoring, (0..num_words).map(|word| Lanes(std::array::from_fn(|lane| all_ones[lane][word]))).collect());
+
+    let mut scores = [0; LANES];
+    let gather_finished_lanes = |kernel: &Kernel<Lanes>, rows: usize, scores: &mut [i32; LANES]| {
+        for (lane, (_, vertical_seq)) in pairs.iter().enumerate() {
+            if vertical_seq.len() == rows {
+                scores[lane] = gather_kernel_score(kernel, |word| word.0[lane], rows, scoring.gap_score);
+            }
+        }
+    };
+
+    gather_finished_lanes(&kernel, 0, &mut scores);
+    for row in 0..num_rows {
+        // the lanes of pairs that are already finished keep calculating on empty match vectors, their score is not used anymore
+        let current_match_vectors: [Option<&Vec<u64>>; LANES] = std::array::from_fn(|lane| {
+            pairs[lane].1.get(row).map(|character| &match_vectors[lane][character])
+        });
+        kernel.next_row(|word| Lanes(current_match_vectors.map(|match_vector| match_vector.map_or(0, |match_vector| match_vector.get(word).copied().unwrap_or(0)))));
+        gather_finished_lanes(&kernel, row + 1, &mut scores);
+    }
+
+    scores
+}
+
+#[cfg(test)]
+mod tests {
+    use scoring::SimpleScoring;
+
+    use crate::{bitpal, bitpal_batch, bitpal_x4};
+    use crate::simd::bitpal_lanes;
+    use crate::tests::generate_sequence_with_alphabet;
+
+    #[test]
+    fn test_bitpal_x4_equals_bitpal() {
+        let mut rng = rand::thread_rng();
+        let mut generate_sequence = |max_len| generate_sequence_with_alphabet(b"ACGT", &mut rng, 0..max_len);
+        for scoring in [SimpleScoring::new(1, -1, -3), SimpleScoring::new(2, -1, -2), SimpleScoring::new(0, -1, -1)] {
+            for _ in 0..100 {
+                let sequences: Vec<(Vec<u8>, Vec<u8>)> = (0..4).map(|_| (generate_sequence(200), generate_sequence(300))).collect();
+                let pairs: [(&[u8], &[u8]); 4] = std::array::from_fn(|lane| (sequences[lane].0.as_slice(), sequences[lane].1.as_slice()));
+                let expected = pairs.map(|(seq1, seq2)| bitpal(seq1, seq2, &scoring));
+                assert_eq!(bitpal_x4(pairs, &scoring), expected);
+                // the portable fallback gives the same result
+                assert_eq!(bitpal_lanes(pairs, &scoring), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_bitpal_batch() {
+        let mut rng = rand::thread_rng();
+        let mut generate_sequence = || generate_sequence_with_alphabet(b"ACGT", &mut rng, 0..100);
+        let scoring = SimpleScoring::new(1, -1, -3);
+        let sequences: Vec<(Vec<u8>, Vec<u8>)> = (0..7).map(|_| (generate_sequence(), generate_sequence())).collect();
+        let pairs: Vec<(&[u8], &[u8])> = sequences.iter().map(|(seq1, seq2)| (seq1.as_slice(), seq2.as_slice())).collect();
+
+        let expected: Vec<i32> = pairs.iter().map(|(seq1, seq2)| bitpal(seq1, seq2, &scoring)).collect();
+        assert_eq!(bitpal_batch(&pairs, &scoring), expected);
+        assert_eq!(bitpal_batch(&[], &scoring), vec![]);
+    }
+}
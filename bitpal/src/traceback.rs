@@ -0,0 +1,166 @@
+use std::cmp::max;
+use std::collections::HashSet;
+
+use needleman_wunsch::{Alignment, AlignmentBuilder};
+use scoring::SimpleScoring;
+
+use crate::kernel::Kernel;
+use crate::{calculate_all_ones, calculate_match_vectors};
+
+/// The optimal global alignment found with BitPAl, together with its score
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitpalAlignment {
+    pub score: i32,
+    pub alignment: Alignment,
+}
+
+/// The delta H classes of every row that BitPAl calculated, from which every cell of the Needleman-Wunsch matrix can be calculated
+struct DeltaRows {
+    gap_score: i32,
+    num_words: usize,
+    num_classes: usize,
+    /// the classes of all rows after each other, every row in the layout of `Kernel::delta_h_row`
+    delta_h: Vec<u64>,
+}
+
+impl DeltaRows {
+    fn row(&self, row: usize) -> &[u64] {
+        let row_len = self.num_words * self.num_classes;
+        &self.delta_h[row * row_len..(row + 1) * row_len]
+    }
+
+    /// The difference between cell (row, col) and the cell to its left, `col` is at least 1
+    fn delta_h(&self, row: usize, col: usize) -> i32 {
+        let (word, bit) = ((col - 1) / 64, (col - 1) % 64);
+        let classes = &self.row(row)[word * self.num_classes..(word + 1) * self.num_classes];
+        let class = classes.iter().position(|&class| (class >> bit) & 1 == 1).expect("Every cell has a delta H class");
+        self.gap_score + class as i32
+    }
+
+    /// The score in cell (row, col): the score in the first column plus all delta H values up to col
+    fn score(&self, row: usize, col: usize) -> i32 {
+        let mut score = row as i32 * self.gap_score;
+        let classes = self.row(row);
+        for (word, mask) in calculate_all_ones(col, self.num_words).into_iter().enumerate() {
+            for class in 0..self.num_classes {
+                score += (self.gap_score + class as i32) * (classes[word * self.num_classes + class] & mask).count_ones() as i32;
+            }
+        }
+        score
+    }
+}
+
+/// Calculate an optimal global alignment with BitPAl, by storing the delta H classes of every row and walking back
+/// from the bottom right corner. Only 1 bit per cell and delta class is stored.
+/// Ties are broken in the same way as `needleman_wunsch::backtrack_alignment`, so both return the same alignment.
+pub fn bitpal_align(seq1: &[u8], seq2: &[u8], scoring: &SimpleScoring) -> BitpalAlignment {
+    // put the shortest sequence horizontally, so it needs as few words as possible
+    let swapped = seq1.len() > seq2.len();
+    let (horizontal_seq, vertical_seq) = if swapped { (seq2, seq1) } else { (seq1, seq2) };
+    let num_words = max(1, horizontal_seq.len().div_ceil(64));
+
+    let mut alphabet: HashSet<u8> = HashSet::new();
+    alphabet.extend(horizontal_seq.iter());
+    alphabet.extend(vertical_seq.iter());
+    let match_vectors = calculate_match_vectors(horizontal_seq, &alphabet);
+
+    let mut kernel = Kernel::new(scoring, calculate_all_ones(horizontal_seq.len(), num_words));
+    let mut delta_h = kernel.delta_h_row().to_vec();
+    for character in vertical_seq {
+        let current_match_vector = &match_vectors[character];
+        kernel.next_row(|word| current_match_vector[word]);
+        delta_h.extend_from_slice(kernel.delta_h_row());
+    }
+    let rows = DeltaRows { gap_score: scoring.gap_score, num_words, num_classes: kernel.num_classes(), delta_h };
+
+    let gap_score = scoring.gap_score;
+    let (mut current_row, mut current_col) = (vertical_seq.len(), horizontal_seq.len());
+    let score = rows.score(current_row, current_col);
+    let mut current_score = score;
+    // the score of the cell above the current one, only recalculated completely when moving to another row
+    let mut up_score = if current_row != 0 { rows.score(current_row - 1, current_col) } else { 0 };
+
+    let mut alignment = AlignmentBuilder::with_capacity(seq1.len() + seq2.len());
+    while current_row != 0 || current_col != 0 {
+        let left = current_col != 0 && (current_row == 0 || rows.delta_h(current_row, current_col) == gap_score);
+        let up = current_row != 0 && (current_col == 0 || current_score == up_score + gap_score);
+        // a gap in seq2 is preferred over a gap in seq1, as in the Needleman-Wunsch backtracking
+        let move_left = if swapped { left && !up } else { left };
+
+        if move_left {
+            let horizontal_char = horizontal_seq[current_col - 1];
+            if swapped { alignment.push_gap_in_seq1(horizontal_char) } else { alignment.push_gap_in_seq2(horizontal_char) }
+            current_score -= rows.delta_h(current_row, current_col);
+            if current_row != 0 {
+                up_score -= rows.delta_h(current_row - 1, current_col);
+            }
+            current_col -= 1;
+            continue;
+        }
+
+        let vertical_char = vertical_seq[current_row - 1];
+        if up {
+            if swapped { alignment.push_gap_in_seq2(vertical_char) } else { alignment.push_gap_in_seq1(vertical_char) }
+        } else {
+            // diagonal case
+            let horizontal_char = horizontal_seq[current_col - 1];
+            if swapped { alignment.push_aligned(vertical_char, horizontal_char) } else { alignment.push_aligned(horizontal_char, vertical_char) }
+            current_col -= 1;
+        }
+        current_row -= 1;
+        current_score = rows.score(current_row, current_col);
+        if current_row != 0 {
+            up_score = rows.score(current_row - 1, current_col);
+        }
+    }
+
+    BitpalAlignment { score, alignment: alignment.finish() }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use fasta_reader::read_fasta;
+    use needleman_wunsch::{backtrack_alignment, construct_matrix};
+    use scoring::SimpleScoring;
+
+    use crate::bitpal_align;
+    use crate::tests::generate_sequence_with_alphabet;
+
+    #[test]
+    fn test_bitpal_align() -> Result<(), Box<dyn Error>> {
+        let (seq1, seq2) = read_fasta("../tests/dna_input.fasta")?;
+        let seq1_chars = seq1.into_bytes();
+        let seq2_chars = seq2.into_bytes();
+        let scoring = SimpleScoring::new(1, -1, -3);
+
+        let matrix = construct_matrix(&seq1_chars, &seq2_chars, &scoring);
+        let res = bitpal_align(&seq1_chars, &seq2_chars, &scoring);
+        assert_eq!(res.score, matrix[(seq2_chars.len(), seq1_chars.len())]);
+        assert_eq!(res.alignment, backtrack_alignment(&matrix, &seq1_chars, &seq2_chars, &scoring));
+
+        let res = bitpal_align(b"", b"ACG", &scoring);
+        assert_eq!(res.score, -9);
+        assert_eq!(res.alignment, (b"---".to_vec(), b"   ".to_vec(), b"ACG".to_vec()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_bitpal_align_fuzzing() {
+        let mut rng = rand::thread_rng();
+        for scoring in [SimpleScoring::new(1, -1, -3), SimpleScoring::new(2, -1, -2), SimpleScoring::new(0, -1, -1), SimpleScoring::new(3, 1, -1)] {
+            // both orientations and sequences that span multiple words
+            for (max_len1, max_len2) in [(20, 20), (150, 60), (60, 150), (300, 300)] {
+                for _ in 0..25 {
+                    let seq1 = generate_sequence_with_alphabet(b"ACGT", &mut rng, 0..max_len1);
+                    let seq2 = generate_sequence_with_alphabet(b"ACGT", &mut rng, 0..max_len2);
+                    let matrix = construct_matrix(&seq1, &seq2, &scoring);
+                    let res = bitpal_align(&seq1, &seq2, &scoring);
+                    assert_eq!(res.score, matrix[(seq2.len(), seq1.len())]);
+                    assert_eq!(res.alignment, backtrack_alignment(&matrix, &seq1, &seq2, &scoring), "{:?}", scoring);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use flate2::bufread::MultiGzDecoder;
+
+/// The first 2 bytes of every gzip (and bgzip) stream
+const GZIP_MAGIC_BYTES: [u8; 2] = [0x1f, 0x8b];
+
+/// Wrap `reader` in a gzip decoder if its content starts with the gzip magic bytes, otherwise return it unchanged.
+/// Bgzip files are supported as well, since they are a series of concatenated gzip members.
+pub fn decompress<R: BufRead + 'static>(mut reader: R) -> io::Result<Box<dyn BufRead>> {
+    if reader.fill_buf()?.starts_with(&GZIP_MAGIC_BYTES) {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Open the file at `path` for reading, transparently decompressing it if it is gzip or bgzip compressed
+pub fn open_input<P: AsRef<Path>>(path: P) -> io::Result<Box<dyn BufRead>> {
+    decompress(BufReader::new(File::open(path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+    use std::io::{Cursor, Read, Write};
+
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+
+    use crate::compression::decompress;
+
+    fn gzip(content: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(content).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_decompress_plain() -> Result<(), Box<dyn Error>> {
+        let mut content = String::new();
+        decompress(Cursor::new(">seq1\nGATTACA\n"))?.read_to_string(&mut content)?;
+        assert_eq!(content, ">seq1\nGATTACA\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decompress_multiple_members() -> Result<(), Box<dyn Error>> {
+        // bgzip writes the file as a series of independent gzip blocks
+        let mut compressed = gzip(b">seq1\nGATTACA\n");
+        compressed.extend(gzip(b">seq2\nGCATGCU\n"));
+
+        let mut content = String::new();
+        decompress(Cursor::new(compressed))?.read_to_string(&mut content)?;
+        assert_eq!(content, ">seq1\nGATTACA\n>seq2\nGCATGCU\n");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,408 @@
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::line_reader::LineReader;
+use crate::{is_valid_residue, FastaError, Record};
+
+/// A single line of a `.fai` index, describing where the sequence of one record is stored in the FASTA file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FaiEntry {
+    /// The id of the record
+    pub name: String,
+    /// The total number of bases in the sequence
+    pub length: usize,
+    /// The byte offset of the first base of the sequence in the file
+    pub offset: u64,
+    /// The number of bases on each line
+    pub line_bases: usize,
+    /// The number of bytes of each line, including the line ending
+    pub line_width: usize,
+}
+
+impl FaiEntry {
+    /// The byte offset of the base at (0-based) `position` in the file
+    fn position_offset(&self, position: usize) -> u64 {
+        self.offset + ((position / self.line_bases) * self.line_width + position % self.line_bases) as u64
+    }
+}
+
+/// A FASTA index, compatible with the `.fai` files created by `samtools faidx`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct FastaIndex {
+    entries: Vec<FaiEntry>,
+    /// Maps the name of a record to its position in `entries`
+    positions: HashMap<String, usize>,
+}
+
+impl FastaIndex {
+    fn push(&mut self, entry: FaiEntry) {
+        self.positions.insert(entry.name.clone(), self.entries.len());
+        self.entries.push(entry);
+    }
+
+    /// The entries in the order they occur in the FASTA file
+    pub fn entries(&self) -> &[FaiEntry] {
+        &self.entries
+    }
+
+    /// The entry of the record with the given name
+    pub fn get(&self, name: &str) -> Option<&FaiEntry> {
+        self.positions.get(name).map(|&position| &self.entries[position])
+    }
+
+    /// Create the index by scanning through an uncompressed FASTA file
+    pub fn build<R: BufRead>(reader: R) -> Result<FastaIndex, FastaError> {
+        let mut lines = LineReader::new(reader);
+        let mut index = FastaIndex::default();
+        // the record that is currently being indexed, and whether we already saw its last (shorter) line
+        let mut current: Option<(FaiEntry, bool)> = None;
+
+        while lines.read_line()? {
+            if let Some(header) = lines.header(b'>') {
+                if let Some((entry, _)) = current.take() {
+                    index.push(entry);
+                }
+                let entry = FaiEntry { name: Record::from_header(&header).id, length: 0, offset: lines.offset, line_bases: 0, line_width: 0 };
+                current = Some((entry, false));
+                continue;
+            }
+
+            let bases = lines.line.len();
+            let Some((entry, last_line_seen)) = current.as_mut() else {
+                if lines.line.trim_ascii().is_empty() {
+                    continue;
+                }
+                return Err(FastaError::MissingHeader { line: lines.line_number });
+            };
+
+            // an empty line can only occur after the last line of the sequence
+            if bases == 0 {
+                *last_line_seen = true;
+                continue;
+            }
+            if *last_line_seen || (entry.line_bases != 0 && bases > entry.line_bases) {
+                return Err(FastaError::IrregularLineLength { id: entry.name.clone(), line: lines.line_number });
+            }
+
+            if entry.line_bases == 0 {
+                entry.line_bases = bases;
+                entry.line_width = lines.line_length;
+            } else if bases < entry.line_bases {
+                *last_line_seen = true;
+            }
+            entry.length += bases;
+        }
+
+        if let Some((entry, _)) = current {
+            index.push(entry);
+        }
+
+        Ok(index)
+    }
+
+    /// Read the content of a `.fai` file
+    pub fn read<R: BufRead>(reader: R) -> Result<FastaIndex, FastaError> {
+        let mut index = FastaIndex::default();
+        for (line_index, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+
+            let invalid_index = || FastaError::InvalidIndex { line: line_index + 1 };
+            let fields: Vec<&str> = line.split('\t').collect();
+            // FASTQ indexes have an additional 6th column with the quality offset
+            if fields.len() < 5 {
+                return Err(invalid_index());
+            }
+            let entry = FaiEntry {
+                name: fields[0].to_string(),
+                length: fields[1].parse().map_err(|_| invalid_index())?,
+                offset: fields[2].parse().map_err(|_| invalid_index())?,
+                line_bases: fields[3].parse().map_err(|_| invalid_index())?,
+                line_width: fields[4].parse().map_err(|_| invalid_index())?,
+            };
+            // the offsets of the bases can't be calculated without bases on a line, or with lines shorter than their bases
+            if (entry.length > 0 && entry.line_bases == 0) || entry.line_width < entry.line_bases {
+                return Err(invalid_index());
+            }
+            index.push(entry);
+        }
+
+        Ok(index)
+    }
+
+    /// Write the index in the `.fai` format
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for entry in &self.entries {
+            writeln!(writer, "{}\t{}\t{}\t{}\t{}", entry.name, entry.length, entry.offset, entry.line_bases, entry.line_width)?;
+        }
+        writer.flush()
+    }
+}
+
+/// The path of the index file that belongs to the FASTA file at `path`: the same path with `.fai` appended
+pub fn fai_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    let mut fai_path = path.as_ref().as_os_str().to_owned();
+    fai_path.push(".fai");
+    PathBuf::from(fai_path)
+}
+
+/// A region of a sequence, written as `name`, `name:start` or `name:start-end`,
+/// where start and end are 1-based and inclusive (the `samtools` convention).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    pub name: String,
+    /// 0-based start of the region
+    pub start: usize,
+    /// 0-based, exclusive end of the region, None to continue until the end of the sequence
+    pub end: Option<usize>,
+}
+
+impl FromStr for Region {
+    type Err = FastaError;
+
+    fn from_str(region: &str) -> Result<Self, Self::Err> {
+        let invalid_region = || FastaError::InvalidRegion { region: region.to_string() };
+        // positions may be written with thousands separators, e.g. chr1:1,000-2,000
+        let parse_position = |position: &str| position.replace(',', "").parse::<usize>().map_err(|_| invalid_region());
+
+        let Some((name, range)) = region.rsplit_once(':') else {
+            return Ok(Region { name: region.to_string(), start: 0, end: None });
+        };
+        let (start, end) = match range.split_once('-') {
+            Some((start, end)) => (parse_position(start)?, Some(parse_position(end)?)),
+            None => (parse_position(range)?, None),
+        };
+        if name.is_empty() || start == 0 || end.is_some_and(|end| end < start) {
+            return Err(invalid_region());
+        }
+
+        Ok(Region { name: name.to_string(), start: start - 1, end })
+    }
+}
+
+impl Region {
+    /// Parse `region` like `from_str`, but if it is the exact name of a sequence in `index` the whole sequence is selected,
+    /// so names that contain a `:` themselves (e.g. `HLA-A*01:01`) are not split into a name and a range
+    pub fn parse_with_index(region: &str, index: &FastaIndex) -> Result<Region, FastaError> {
+        if index.get(region).is_some() {
+            return Ok(Region { name: region.to_string(), start: 0, end: None });
+        }
+        region.parse()
+    }
+}
+
+/// Reader that fetches regions of a FASTA file without reading the whole file, using its index
+pub struct IndexedFastaReader<R: Read + Seek> {
+    reader: R,
+    index: FastaIndex,
+}
+
+impl<R: Read + Seek> IndexedFastaReader<R> {
+    pub fn new(reader: R, index: FastaIndex) -> IndexedFastaReader<R> {
+        IndexedFastaReader { reader, index }
+    }
+
+    pub fn index(&self) -> &FastaIndex {
+        &self.index
+    }
+
+    /// Fetch the bases in the 0-based, half-open range `start..end` of the sequence with the given name
+    pub fn fetch(&mut self, name: &str, start: usize, end: usize) -> Result<Vec<u8>, FastaError> {
+        let entry = self.index.get(name).ok_or_else(|| FastaError::UnknownSequence { name: name.to_string() })?;
+        if start > end || end > entry.length {
+            return Err(FastaError::InvalidRegion { region: format!("{}:{}-{}", name, start + 1, end) });
+        }
+        if start == end {
+            return Ok(vec![]);
+        }
+
+        let start_offset = entry.position_offset(start);
+        let end_offset = entry.position_offset(end - 1) + 1;
+        let mut bytes = vec![0; (end_offset - start_offset) as usize];
+        self.reader.seek(SeekFrom::Start(start_offset))?;
+        self.reader.read_exact(&mut bytes)?;
+        // a stale or mismatched index points at other bytes than the sequence, such as a header
+        if let Some(position) = bytes.iter().position(|&byte| byte != b'\n' && byte != b'\r' && !is_valid_residue(byte)) {
+            let (line, column) = self.line_and_column(start_offset + position as u64)?;
+            return Err(FastaError::InvalidResidue { residue: bytes[position], line, column });
+        }
+        bytes.retain(|&byte| byte != b'\n' && byte != b'\r');
+
+        Ok(bytes)
+    }
+
+    /// The 1-based line and column of the byte at `offset` in the file, only needed to report an invalid residue
+    fn line_and_column(&mut self, offset: u64) -> Result<(usize, usize), FastaError> {
+        self.reader.rewind()?;
+        let (mut line, mut line_start) = (1, 0);
+        for (position, byte) in BufReader::new(self.reader.by_ref().take(offset)).bytes().enumerate() {
+            if byte? == b'\n' {
+                line += 1;
+                line_start = position as u64 + 1;
+            }
+        }
+        Ok((line, (offset - line_start) as usize + 1))
+    }
+
+    /// Fetch the bases of `region`
+    pub fn fetch_region(&mut self, region: &Region) -> Result<Vec<u8>, FastaError> {
+        let end = match region.end {
+            Some(end) => end,
+            None => self.index.get(&region.name).ok_or_else(|| FastaError::UnknownSequence { name: region.name.clone() })?.length,
+        };
+        self.fetch(&region.name, region.start, end)
+    }
+}
+
+impl IndexedFastaReader<File> {
+    /// Open the FASTA file at `path` together with its `.fai` index.
+    /// If the index does not exist yet, it is created and stored next to the FASTA file.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<IndexedFastaReader<File>, FastaError> {
+        let mut file = File::open(&path)?;
+        let mut magic_bytes = [0; 2];
+        if file.read(&mut magic_bytes)? == 2 && magic_bytes == [0x1f, 0x8b] {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "indexed access requires an uncompressed FASTA file").into());
+        }
+        file.rewind()?;
+
+        let fai_path = fai_path(&path);
+        let index = match File::open(&fai_path) {
+            Ok(fai_file) => FastaIndex::read(BufReader::new(fai_file))?,
+            Err(_) => {
+                let index = FastaIndex::build(BufReader::new(&file))?;
+                file.rewind()?;
+                // the stored index is a best-effort cache that only speeds up later runs, so failing to store it
+                // (e.g. in a read-only directory or a full disk) is not an error, but a partially written index is
+                // removed again, since later runs would read it instead of building a complete one
+                if let Ok(fai_file) = File::create(&fai_path) {
+                    if index.write(BufWriter::new(fai_file)).is_err() {
+                        let _ = fs::remove_file(&fai_path);
+                    }
+                }
+                index
+            }
+        };
+
+        Ok(IndexedFastaReader::new(file, index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+    use std::io::Cursor;
+
+    use crate::fai::{FaiEntry, FastaIndex, IndexedFastaReader, Region};
+    use crate::FastaError;
+
+    const FASTA: &str = ">chr1 first\nACGTA\nCGTAC\nGT\n>chr2\r\nTTGG\r\nCC\r\n";
+
+    #[test]
+    fn test_build_index() -> Result<(), Box<dyn Error>> {
+        let index = FastaIndex::build(Cursor::new(FASTA))?;
+        assert_eq!(index.entries(), &[
+            FaiEntry { name: "chr1".to_string(), length: 12, offset: 12, line_bases: 5, line_width: 6 },
+            FaiEntry { name: "chr2".to_string(), length: 6, offset: 34, line_bases: 4, line_width: 6 },
+        ]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_and_read_index() -> Result<(), Box<dyn Error>> {
+        let index = FastaIndex::build(Cursor::new(FASTA))?;
+        let mut fai = vec![];
+        index.write(&mut fai)?;
+        assert_eq!(String::from_utf8(fai.clone())?, "chr1\t12\t12\t5\t6\nchr2\t6\t34\t4\t6\n");
+        assert_eq!(FastaIndex::read(Cursor::new(fai))?, index);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_invalid_index() {
+        assert!(matches!(FastaIndex::read(Cursor::new("chr1\t12\t6\t5\t6\nchr2\t6\t34\t0\t0\n")), Err(FastaError::InvalidIndex { line: 2 })));
+        assert!(matches!(FastaIndex::read(Cursor::new("chr1\t12\t6\t5\t4\n")), Err(FastaError::InvalidIndex { line: 1 })));
+        assert!(matches!(FastaIndex::read(Cursor::new("chr1\t12\t6\t5\n")), Err(FastaError::InvalidIndex { line: 1 })));
+        assert!(FastaIndex::read(Cursor::new("empty\t0\t7\t0\t0\n")).is_ok());
+    }
+
+    #[test]
+    fn test_build_index_irregular_lines() {
+        let res = FastaIndex::build(Cursor::new(">chr1\nACGTA\nCG\nTAC\n"));
+        assert!(matches!(res, Err(FastaError::IrregularLineLength { line: 4, .. })));
+    }
+
+    #[test]
+    fn test_fetch() -> Result<(), Box<dyn Error>> {
+        let index = FastaIndex::build(Cursor::new(FASTA))?;
+        let mut reader = IndexedFastaReader::new(Cursor::new(FASTA), index);
+        assert_eq!(reader.fetch("chr1", 0, 12)?, b"ACGTACGTACGT");
+        assert_eq!(reader.fetch("chr1", 3, 11)?, b"TACGTACG");
+        assert_eq!(reader.fetch("chr2", 2, 6)?, b"GGCC");
+        assert_eq!(reader.fetch("chr2", 4, 4)?, b"");
+        assert!(matches!(reader.fetch("chr2", 2, 7), Err(FastaError::InvalidRegion { .. })));
+        assert!(matches!(reader.fetch("chr3", 0, 1), Err(FastaError::UnknownSequence { .. })));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fetch_invalid_residue() -> Result<(), Box<dyn Error>> {
+        let fasta = ">chr1\nACGTA\nCG7AC\n";
+        let mut reader = IndexedFastaReader::new(Cursor::new(fasta), FastaIndex::build(Cursor::new(fasta))?);
+        assert_eq!(reader.fetch("chr1", 0, 5)?, b"ACGTA");
+        assert!(matches!(reader.fetch("chr1", 3, 8), Err(FastaError::InvalidResidue { residue: b'7', line: 3, column: 3 })));
+
+        // an index of another file makes the reader fetch part of the header
+        let index = FastaIndex::build(Cursor::new(">chr1\nACGT\n"))?;
+        let mut reader = IndexedFastaReader::new(Cursor::new(">chr1 12\nACGT\n"), index);
+        assert!(matches!(reader.fetch("chr1", 0, 4), Err(FastaError::InvalidResidue { residue: b'1', line: 1, column: 7 })));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_region() -> Result<(), Box<dyn Error>> {
+        assert_eq!("chr1".parse::<Region>()?, Region { name: "chr1".to_string(), start: 0, end: None });
+        assert_eq!("chr1:1,000".parse::<Region>()?, Region { name: "chr1".to_string(), start: 999, end: None });
+        assert_eq!("chr1:1000-2000".parse::<Region>()?, Region { name: "chr1".to_string(), start: 999, end: Some(2000) });
+        assert!("chr1:0-10".parse::<Region>().is_err());
+        assert!("chr1:20-10".parse::<Region>().is_err());
+        assert!("chr1:a-b".parse::<Region>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fetch_region() -> Result<(), Box<dyn Error>> {
+        let index = FastaIndex::build(Cursor::new(FASTA))?;
+        let mut reader = IndexedFastaReader::new(Cursor::new(FASTA), index);
+        assert_eq!(reader.fetch_region(&"chr1:5-7".parse()?)?, b"ACG");
+        assert_eq!(reader.fetch_region(&"chr2".parse()?)?, b"TTGGCC");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_region_with_index() -> Result<(), Box<dyn Error>> {
+        let fasta = ">HLA-A*01:01\nACGTACGT\n>chr1\nTTGG\n";
+        let index = FastaIndex::build(Cursor::new(fasta))?;
+        // a name that contains a ':' is looked up as a whole before a range is split off
+        assert_eq!(Region::parse_with_index("HLA-A*01:01", &index)?, Region { name: "HLA-A*01:01".to_string(), start: 0, end: None });
+        assert_eq!(Region::parse_with_index("HLA-A*01:01:3-5", &index)?, Region { name: "HLA-A*01:01".to_string(), start: 2, end: Some(5) });
+        assert_eq!(Region::parse_with_index("chr1:2", &index)?, Region { name: "chr1".to_string(), start: 1, end: None });
+
+        let mut reader = IndexedFastaReader::new(Cursor::new(fasta), index);
+        assert_eq!(reader.fetch_region(&Region::parse_with_index("HLA-A*01:01", reader.index())?)?, b"ACGTACGT");
+
+        Ok(())
+    }
+}
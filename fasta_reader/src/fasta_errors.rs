@@ -0,0 +1,70 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io;
+
+/// Error indicating that a FASTA or FASTQ file could not be parsed.
+/// All line and column numbers are 1-based.
+#[derive(Debug)]
+pub enum FastaError {
+    /// The underlying reader failed
+    Io(io::Error),
+    /// The input starts with something other than a `>` (FASTA) or `@` (FASTQ) header
+    UnknownFormat { found: u8 },
+    /// Sequence data was found where a record header was expected
+    MissingHeader { line: usize },
+    /// The record with the header on the given line does not contain any sequence data
+    EmptyRecord { id: String, line: usize },
+    /// The input contained fewer records than requested
+    NotEnoughRecords { expected: usize, found: usize },
+    /// The sequence contains a character that is not a valid residue
+    InvalidResidue { residue: u8, line: usize, column: usize },
+    /// The input ended before the `+` separator line of the FASTQ record
+    MissingQualities { id: String, line: usize },
+    /// The number of quality values of the FASTQ record does not match its sequence length
+    QualityLengthMismatch { id: String, expected: usize, found: usize },
+    /// The quality string contains a character outside of the Phred+33 range (`!` to `~`)
+    InvalidQuality { quality: u8, line: usize, column: usize },
+    /// The lines of the record can't be indexed, because they don't all have the same length (except the last one)
+    IrregularLineLength { id: String, line: usize },
+    /// The line of the `.fai` index file is malformed
+    InvalidIndex { line: usize },
+    /// The requested sequence does not occur in the index
+    UnknownSequence { name: String },
+    /// The region can't be parsed, or lies outside of its sequence
+    InvalidRegion { region: String },
+}
+
+impl Display for FastaError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FastaError::Io(error) => write!(f, "Failed to read the input: {}", error),
+            FastaError::UnknownFormat { found } => write!(f, "Unknown input format: expected '>' (FASTA) or '@' (FASTQ), but found '{}'", found.escape_ascii()),
+            FastaError::MissingHeader { line } => write!(f, "Line {}: expected a record header before the sequence data", line),
+            FastaError::EmptyRecord { id, line } => write!(f, "Line {}: record '{}' does not contain a sequence", line, id),
+            FastaError::NotEnoughRecords { expected, found } => write!(f, "Expected at least {} records in the input, but only found {}", expected, found),
+            FastaError::InvalidResidue { residue, line, column } => write!(f, "Line {}, column {}: invalid residue '{}'", line, column, residue.escape_ascii()),
+            FastaError::MissingQualities { id, line } => write!(f, "Line {}: record '{}' ends before the '+' separator of the quality values", line, id),
+            FastaError::QualityLengthMismatch { id, expected, found } => write!(f, "Record '{}' has {} quality values for a sequence of length {}", id, found, expected),
+            FastaError::InvalidQuality { quality, line, column } => write!(f, "Line {}, column {}: invalid quality value '{}'", line, column, quality.escape_ascii()),
+            FastaError::IrregularLineLength { id, line } => write!(f, "Line {}: record '{}' can't be indexed, since its lines have different lengths", line, id),
+            FastaError::InvalidIndex { line } => write!(f, "Line {} of the FASTA index is malformed", line),
+            FastaError::UnknownSequence { name } => write!(f, "Sequence '{}' does not occur in the FASTA index", name),
+            FastaError::InvalidRegion { region } => write!(f, "Invalid region '{}'", region),
+        }
+    }
+}
+
+impl Error for FastaError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FastaError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for FastaError {
+    fn from(error: io::Error) -> Self {
+        FastaError::Io(error)
+    }
+}
@@ -0,0 +1,154 @@
+use std::io;
+use std::io::BufRead;
+use std::path::Path;
+
+use crate::line_reader::LineReader;
+use crate::{open_input, push_residues, FastaError, Record};
+
+/// Check whether a character is a valid Phred+33 encoded quality value
+fn is_valid_quality(quality: u8) -> bool {
+    (b'!'..=b'~').contains(&quality)
+}
+
+/// Streaming reader that yields every record of a FASTQ file, one at a time.
+/// Supports both the common 4-line layout and records where the sequence and quality strings are wrapped over multiple lines.
+pub struct FastqReader<R: BufRead> {
+    lines: LineReader<R>,
+    finished: bool,
+}
+
+impl<R: BufRead> FastqReader<R> {
+    pub fn new(reader: R) -> FastqReader<R> {
+        FastqReader::from_lines(LineReader::new(reader))
+    }
+
+    pub(crate) fn from_lines(lines: LineReader<R>) -> FastqReader<R> {
+        FastqReader { lines, finished: false }
+    }
+
+    fn read_record(&mut self) -> Result<Option<Record>, FastaError> {
+        // find the header of this record, skipping empty lines between records
+        let (header, header_line) = loop {
+            if !self.lines.read_line()? {
+                return Ok(None);
+            }
+            if let Some(header) = self.lines.header(b'@') {
+                break (header, self.lines.line_number);
+            }
+            if !self.lines.line.trim_ascii().is_empty() {
+                return Err(FastaError::MissingHeader { line: self.lines.line_number });
+            }
+        };
+
+        // the sequence runs until the '+' separator line
+        let mut record = Record::from_header(&header);
+        loop {
+            if !self.lines.read_line()? {
+                return Err(FastaError::MissingQualities { id: record.id, line: self.lines.line_number });
+            }
+            if self.lines.line.starts_with(b"+") {
+                break;
+            }
+            push_residues(&self.lines.line, self.lines.line_number, &mut record.seq)?;
+        }
+
+        if record.seq.is_empty() {
+            return Err(FastaError::EmptyRecord { id: record.id, line: header_line });
+        }
+
+        // the quality string can start with '@' as well, so it is delimited by the length of the sequence instead of the next header
+        let mut qual = Vec::with_capacity(record.seq.len());
+        while qual.len() < record.seq.len() && self.lines.read_line()? {
+            for (index, &quality) in self.lines.line.trim_ascii_end().iter().enumerate() {
+                if !is_valid_quality(quality) {
+                    return Err(FastaError::InvalidQuality { quality, line: self.lines.line_number, column: index + 1 });
+                }
+                qual.push(quality);
+            }
+        }
+
+        if qual.len() != record.seq.len() {
+            return Err(FastaError::QualityLengthMismatch { id: record.id, expected: record.seq.len(), found: qual.len() });
+        }
+        record.qual = Some(qual);
+
+        Ok(Some(record))
+    }
+}
+
+impl FastqReader<Box<dyn BufRead>> {
+    /// Open the (optionally gzip compressed) file at `path` and create a reader over its records
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<FastqReader<Box<dyn BufRead>>> {
+        Ok(FastqReader::new(open_input(path)?))
+    }
+}
+
+impl<R: BufRead> Iterator for FastqReader<R> {
+    type Item = Result<Record, FastaError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let result = self.read_record().transpose();
+        // stop after the last record or after the first error, since we can't recover the position in the file
+        if !matches!(result, Some(Ok(_))) {
+            self.finished = true;
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+    use std::io::Cursor;
+
+    use crate::{FastaError, FastqReader, Record};
+
+    #[test]
+    fn fastq_reader_test() -> Result<(), Box<dyn Error>> {
+        let records = FastqReader::from_file("../tests/input.fastq")?.collect::<Result<Vec<Record>, FastaError>>()?;
+
+        assert_eq!(records, vec![
+            Record { id: "seq1".to_string(), description: Some("first read".to_string()), seq: b"GATTACA".to_vec(), qual: Some(b"IIIIIII".to_vec()) },
+            Record { id: "seq2".to_string(), description: None, seq: b"GCATGCU".to_vec(), qual: Some(b"@@@@@@@".to_vec()) },
+        ]);
+        assert_eq!(records[0].phred_scores(), Some(vec![40; 7]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn fastq_reader_multiline_test() -> Result<(), Box<dyn Error>> {
+        let records = FastqReader::from_file("../tests/multiline_input.fastq")?.collect::<Result<Vec<Record>, FastaError>>()?;
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].seq, b"GATTACA");
+        assert_eq!(records[0].qual.as_deref(), Some(&b"@IIIIII"[..]));
+        assert_eq!(records[1].seq, b"GCATGCU");
+        assert_eq!(records[1].qual.as_deref(), Some(&b"IIII@@@"[..]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn fastq_reader_quality_length_mismatch_test() {
+        let mut reader = FastqReader::new(Cursor::new("@seq1\nGATTACA\n+\nIIII\n"));
+        assert!(matches!(reader.next(), Some(Err(FastaError::QualityLengthMismatch { expected: 7, found: 4, .. }))));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn fastq_reader_missing_qualities_test() {
+        let mut reader = FastqReader::new(Cursor::new("@seq1\nGATTACA\n"));
+        assert!(matches!(reader.next(), Some(Err(FastaError::MissingQualities { line: 2, .. }))));
+    }
+
+    #[test]
+    fn fastq_reader_invalid_quality_test() {
+        let mut reader = FastqReader::new(Cursor::new("@seq1\nGATTACA\n+\nIII III\n"));
+        assert!(matches!(reader.next(), Some(Err(FastaError::InvalidQuality { quality: b' ', line: 4, column: 4 }))));
+    }
+}
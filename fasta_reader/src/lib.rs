@@ -1,40 +1,225 @@
-use std::error::Error;
-use std::fs::File;
 use std::io;
 use std::io::BufRead;
-use std::path::{Path};
+use std::path::Path;
 
-fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
-    where P: AsRef<Path>, {
-    let file = File::open(filename)?;
-    Ok(io::BufReader::new(file).lines())
+pub use crate::compression::{decompress, open_input};
+pub use crate::fai::{fai_path, FaiEntry, FastaIndex, IndexedFastaReader, Region};
+pub use crate::fasta_errors::FastaError;
+pub use crate::fastq::FastqReader;
+use crate::line_reader::LineReader;
+
+mod compression;
+mod fai;
+mod fasta_errors;
+mod fastq;
+mod line_reader;
+
+/// A single record of a FASTA or FASTQ file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    /// The identifier of the record: the header up to the first whitespace
+    pub id: String,
+    /// The remainder of the header after the identifier, if there is any
+    pub description: Option<String>,
+    /// The sequence, with all line breaks removed
+    pub seq: Vec<u8>,
+    /// The Phred+33 encoded quality string for FASTQ records, None for FASTA records
+    pub qual: Option<Vec<u8>>,
+}
+
+impl Record {
+    /// Split a header line (without the leading `>` or `@`) into the id and the description
+    fn from_header(header: &str) -> Record {
+        let header = header.trim();
+        let (id, description) = match header.split_once(char::is_whitespace) {
+            Some((id, description)) => (id, Some(description.trim_start().to_string())),
+            None => (header, None),
+        };
+
+        Record { id: id.to_string(), description, seq: vec![], qual: None }
+    }
+
+    /// The decoded Phred quality scores of this record, if it has a quality string
+    pub fn phred_scores(&self) -> Option<Vec<u8>> {
+        self.qual.as_ref().map(|qual| qual.iter().map(|quality| quality - b'!').collect())
+    }
 }
 
-pub fn read_fasta(file: &str) -> Result<(String, String), Box<dyn Error>> {
-    let mut sequences: Vec<String> = vec![];
+/// Check whether a character can occur in a sequence: letters, `*` (stop codon) and `-` (gap)
+fn is_valid_residue(residue: u8) -> bool {
+    residue.is_ascii_alphabetic() || residue == b'*' || residue == b'-'
+}
+
+/// Append the residues of `line` to `seq`, skipping whitespace.
+/// Fails on the first character that is not a valid residue.
+fn push_residues(line: &[u8], line_number: usize, seq: &mut Vec<u8>) -> Result<(), FastaError> {
+    for (index, &residue) in line.iter().enumerate() {
+        if residue.is_ascii_whitespace() {
+            continue;
+        }
+        if !is_valid_residue(residue) {
+            return Err(FastaError::InvalidResidue { residue, line: line_number, column: index + 1 });
+        }
+        seq.push(residue);
+    }
+
+    Ok(())
+}
 
-    let mut current_sequence = String::new();
-    for line in read_lines(file)?.flatten() {
-        // skip header and push currently accumulated protein (if it is not empty, since that happens when, encountering the first header)
-        if line.starts_with('>') {
-            if !current_sequence.is_empty() {
-                sequences.push(current_sequence);
-                current_sequence = String::new();
+/// Streaming reader that yields every record of a FASTA file, one at a time.
+/// Works on anything that implements `BufRead` (files, stdin, in-memory buffers, ...)
+pub struct FastaReader<R: BufRead> {
+    lines: LineReader<R>,
+    /// Header (and its line number) of the next record, which was already consumed while reading the previous record
+    next_header: Option<(String, usize)>,
+    finished: bool,
+}
+
+impl<R: BufRead> FastaReader<R> {
+    pub fn new(reader: R) -> FastaReader<R> {
+        FastaReader::from_lines(LineReader::new(reader))
+    }
+
+    fn from_lines(lines: LineReader<R>) -> FastaReader<R> {
+        FastaReader { lines, next_header: None, finished: false }
+    }
+
+    fn read_record(&mut self) -> Result<Option<Record>, FastaError> {
+        // find the header of this record, skipping leading empty lines
+        let (header, header_line) = match self.next_header.take() {
+            Some(header) => header,
+            None => loop {
+                if !self.lines.read_line()? {
+                    return Ok(None);
+                }
+                if let Some(header) = self.lines.header(b'>') {
+                    break (header, self.lines.line_number);
+                }
+                if !self.lines.line.trim_ascii().is_empty() {
+                    return Err(FastaError::MissingHeader { line: self.lines.line_number });
+                }
+            }
+        };
+
+        let mut record = Record::from_header(&header);
+        while self.lines.read_line()? {
+            if let Some(header) = self.lines.header(b'>') {
+                self.next_header = Some((header, self.lines.line_number));
+                break;
             }
-        } else {
-            current_sequence += line.strip_suffix('\n').unwrap_or(&*line);
+            push_residues(&self.lines.line, self.lines.line_number, &mut record.seq)?;
+        }
+
+        if record.seq.is_empty() {
+            return Err(FastaError::EmptyRecord { id: record.id, line: header_line });
+        }
+
+        Ok(Some(record))
+    }
+}
+
+impl FastaReader<Box<dyn BufRead>> {
+    /// Open the (optionally gzip compressed) file at `path` and create a reader over its records
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<FastaReader<Box<dyn BufRead>>> {
+        Ok(FastaReader::new(open_input(path)?))
+    }
+}
+
+impl<R: BufRead> Iterator for FastaReader<R> {
+    type Item = Result<Record, FastaError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let result = self.read_record().transpose();
+        // stop after the last record or after the first error, since we can't recover the position in the file
+        if !matches!(result, Some(Ok(_))) {
+            self.finished = true;
+        }
+        result
+    }
+}
+
+/// Reader over the records of a FASTA or FASTQ file.
+/// The format is detected from the first character of the input: `>` for FASTA and `@` for FASTQ.
+pub enum SequenceReader<R: BufRead> {
+    Fasta(FastaReader<R>),
+    Fastq(FastqReader<R>),
+}
+
+impl<R: BufRead> SequenceReader<R> {
+    pub fn new(reader: R) -> Result<SequenceReader<R>, FastaError> {
+        let mut lines = LineReader::new(reader);
+        match lines.peek_first_byte()? {
+            // an empty input is treated as a FASTA file without records
+            Some(b'>') | None => Ok(SequenceReader::Fasta(FastaReader::from_lines(lines))),
+            Some(b'@') => Ok(SequenceReader::Fastq(FastqReader::from_lines(lines))),
+            Some(found) => Err(FastaError::UnknownFormat { found }),
         }
     }
-    sequences.push(current_sequence);
+}
+
+impl SequenceReader<Box<dyn BufRead>> {
+    /// Open the (optionally gzip compressed) file at `path` and create a reader over its records
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<SequenceReader<Box<dyn BufRead>>, FastaError> {
+        SequenceReader::new(open_input(path)?)
+    }
+}
 
-    let seq1 = sequences.swap_remove(0);
-    let seq2 = sequences.swap_remove(0);
+impl<R: BufRead> Iterator for SequenceReader<R> {
+    type Item = Result<Record, FastaError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            SequenceReader::Fasta(reader) => reader.next(),
+            SequenceReader::Fastq(reader) => reader.next(),
+        }
+    }
+}
+
+/// Read the first `count` records of a (optionally gzip compressed) FASTA or FASTQ file
+pub fn read_records<P: AsRef<Path>>(file: P, count: usize) -> Result<Vec<Record>, FastaError> {
+    let records = SequenceReader::from_file(file)?.take(count).collect::<Result<Vec<Record>, FastaError>>()?;
+    if records.len() < count {
+        return Err(FastaError::NotEnoughRecords { expected: count, found: records.len() });
+    }
+
+    Ok(records)
+}
+
+/// Read the first 2 sequences of a (optionally gzip compressed) FASTA or FASTQ file
+pub fn read_fasta(file: &str) -> Result<(String, String), FastaError> {
+    let mut sequences = read_records(file, 2)?
+        .into_iter()
+        // residues are validated to be ASCII while reading, so every byte is a valid char
+        .map(|record| record.seq.into_iter().map(char::from).collect::<String>());
+    let seq1 = sequences.next().unwrap();
+    let seq2 = sequences.next().unwrap();
 
     Ok((seq1, seq2))
 }
 
+/// Fetch 2 sequences from an uncompressed FASTA file using its `.fai` index (which is created if it does not exist yet).
+/// Each region selects one of the sequences and is written as `name`, `name:start` or `name:start-end` (1-based, inclusive),
+/// where a name that occurs in the index as a whole is never split into a name and a range.
+pub fn read_fasta_regions(file: &str, region1: &str, region2: &str) -> Result<(String, String), FastaError> {
+    let mut reader = IndexedFastaReader::open(file)?;
+    let region1 = Region::parse_with_index(region1, reader.index())?;
+    let region2 = Region::parse_with_index(region2, reader.index())?;
+    let seq1 = reader.fetch_region(&region1)?;
+    let seq2 = reader.fetch_region(&region2)?;
+
+    // residues are validated to be ASCII while fetching, so every byte is a valid char
+    Ok((seq1.into_iter().map(char::from).collect(), seq2.into_iter().map(char::from).collect()))
+}
+
 #[cfg(test)]
 mod tests {
+    use std::error::Error;
+    use std::io::Cursor;
+
     use super::*;
 
     #[test]
@@ -54,4 +239,119 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn read_fasta_single_record_test() {
+        let res = read_fasta("../tests/single_input.fasta");
+        assert!(matches!(res, Err(FastaError::NotEnoughRecords { expected: 2, found: 1 })));
+    }
+
+    #[test]
+    fn fasta_reader_all_records_test() -> Result<(), Box<dyn Error>> {
+        let input = ">seq1 first sequence\nGAT\nTACA\n\n>seq2\r\nGCATGCU\r\n>seq3  third\nMK*\n";
+        let records = FastaReader::new(Cursor::new(input)).collect::<Result<Vec<Record>, FastaError>>()?;
+
+        assert_eq!(records, vec![
+            Record { id: "seq1".to_string(), description: Some("first sequence".to_string()), seq: b"GATTACA".to_vec(), qual: None },
+            Record { id: "seq2".to_string(), description: None, seq: b"GCATGCU".to_vec(), qual: None },
+            Record { id: "seq3".to_string(), description: Some("third".to_string()), seq: b"MK*".to_vec(), qual: None },
+        ]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn fasta_reader_large_file_test() -> Result<(), Box<dyn Error>> {
+        let records = FastaReader::from_file("../tests/verylarge.fasta")?.collect::<Result<Vec<Record>, FastaError>>()?;
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].id, "sequence");
+        assert_eq!(records[0].description.as_deref(), Some("1"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn fasta_reader_missing_header_test() {
+        let mut reader = FastaReader::new(Cursor::new("\nGATTACA\n>seq1\nGCATGCU\n"));
+        assert!(matches!(reader.next(), Some(Err(FastaError::MissingHeader { line: 2 }))));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn fasta_reader_empty_input_test() {
+        assert!(FastaReader::new(Cursor::new("")).next().is_none());
+    }
+
+    #[test]
+    fn fasta_reader_empty_record_test() {
+        let mut reader = FastaReader::new(Cursor::new(">seq1\nGATTACA\n>seq2\n\n>seq3\nGCATGCU\n"));
+        assert!(matches!(reader.next(), Some(Ok(_))));
+        assert!(matches!(reader.next(), Some(Err(FastaError::EmptyRecord { id, line: 3 })) if id == "seq2"));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn fasta_reader_invalid_residue_test() {
+        let mut reader = FastaReader::new(Cursor::new(">seq1\nGATTACA\nGA7TACA\n"));
+        assert!(matches!(reader.next(), Some(Err(FastaError::InvalidResidue { residue: b'7', line: 3, column: 3 }))));
+    }
+
+    #[test]
+    fn fasta_reader_invalid_utf8_test() {
+        let mut reader = FastaReader::new(Cursor::new(b">seq1\nGAT\xffACA\n".to_vec()));
+        assert!(matches!(reader.next(), Some(Err(FastaError::InvalidResidue { residue: 0xff, line: 2, column: 4 }))));
+    }
+
+    #[test]
+    fn read_fastq_test() -> Result<(), Box<dyn Error>> {
+        let (seq1, seq2) = read_fasta("../tests/input.fastq")?;
+        assert_eq!(seq1, "GATTACA");
+        assert_eq!(seq2, "GCATGCU");
+
+        Ok(())
+    }
+
+    #[test]
+    fn sequence_reader_detect_format_test() -> Result<(), Box<dyn Error>> {
+        assert!(matches!(SequenceReader::new(Cursor::new("\n>seq1\nGATTACA\n"))?, SequenceReader::Fasta(_)));
+        assert!(matches!(SequenceReader::new(Cursor::new("@seq1\nGATTACA\n+\nIIIIIII\n"))?, SequenceReader::Fastq(_)));
+        assert!(matches!(SequenceReader::new(Cursor::new("GATTACA\n")), Err(FastaError::UnknownFormat { found: b'G' })));
+
+        Ok(())
+    }
+
+    #[test]
+    fn sequence_reader_line_numbers_test() -> Result<(), Box<dyn Error>> {
+        let mut reader = SequenceReader::new(Cursor::new("\n\n>seq1\nGA7TACA\n"))?;
+        assert!(matches!(reader.next(), Some(Err(FastaError::InvalidResidue { residue: b'7', line: 4, column: 3 }))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_compressed_fasta_test() -> Result<(), Box<dyn Error>> {
+        let (seq1, seq2) = read_fasta("../tests/input.fasta.gz")?;
+        assert_eq!(seq1, "GATTACA");
+        assert_eq!(seq2, "GCATGCU");
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_compressed_fastq_test() -> Result<(), Box<dyn Error>> {
+        let (seq1, seq2) = read_fasta("../tests/input.fq.gz")?;
+        assert_eq!(seq1, "GATTACA");
+        assert_eq!(seq2, "GCATGCU");
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_fasta_regions_test() -> Result<(), Box<dyn Error>> {
+        let (seq1, seq2) = read_fasta_regions("../tests/indexed_input.fasta", "chr1:18-45", "read1")?;
+        assert_eq!(seq1, "GATAAAAATTTCCAGTACGATAGGTTGC");
+        assert_eq!(seq2, "ACGATACGCGGCAG");
+
+        Ok(())
+    }
 }
@@ -0,0 +1,63 @@
+use std::io;
+use std::io::BufRead;
+
+/// Line based reader shared by the FASTA and FASTQ parsers that keeps track of the current line number
+pub(crate) struct LineReader<R: BufRead> {
+    reader: R,
+    /// The last line that was read, without the line ending
+    pub(crate) line: Vec<u8>,
+    /// The 1-based number of the last line that was read
+    pub(crate) line_number: usize,
+    /// The number of bytes of the last line that was read, including the line ending
+    pub(crate) line_length: usize,
+    /// The number of bytes consumed from the input so far
+    pub(crate) offset: u64,
+}
+
+impl<R: BufRead> LineReader<R> {
+    pub(crate) fn new(reader: R) -> LineReader<R> {
+        LineReader { reader, line: vec![], line_number: 0, line_length: 0, offset: 0 }
+    }
+
+    /// Read the next line into `self.line`, without the line ending. Returns false at the end of the input.
+    pub(crate) fn read_line(&mut self) -> io::Result<bool> {
+        self.line.clear();
+        self.line_length = self.reader.read_until(b'\n', &mut self.line)?;
+        if self.line_length == 0 {
+            return Ok(false);
+        }
+        self.line_number += 1;
+        self.offset += self.line_length as u64;
+        while let Some(b'\n' | b'\r') = self.line.last() {
+            self.line.pop();
+        }
+        Ok(true)
+    }
+
+    /// Skip all leading whitespace and return the first byte after it, without consuming that byte.
+    /// Returns None at the end of the input.
+    pub(crate) fn peek_first_byte(&mut self) -> io::Result<Option<u8>> {
+        loop {
+            let buffer = self.reader.fill_buf()?;
+            let Some(&first_byte) = buffer.first() else {
+                return Ok(None);
+            };
+            if !first_byte.is_ascii_whitespace() {
+                return Ok(Some(first_byte));
+            }
+            if first_byte == b'\n' {
+                self.line_number += 1;
+            }
+            self.reader.consume(1);
+            self.offset += 1;
+        }
+    }
+
+    /// The content of `self.line` after `prefix` as a string, if the line starts with `prefix`
+    pub(crate) fn header(&self, prefix: u8) -> Option<String> {
+        match self.line.split_first() {
+            Some((&first, header)) if first == prefix => Some(String::from_utf8_lossy(header).into_owned()),
+            _ => None,
+        }
+    }
+}
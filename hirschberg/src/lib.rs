@@ -1,9 +1,15 @@
 use std::cmp::max;
 use std::error::Error;
-use needleman_wunsch::{backtrack_alignment, construct_matrix};
+use needleman_wunsch::{backtrack_alignment, construct_matrix, Alignment};
+use scoring::Scoring;
+
+pub use crate::myers_miller::myers_miller;
+
+mod myers_miller;
 
 /// Calculate the Needleman Wunsch score only using 2 rows
-pub fn nw_score(seq1: &[u8], seq2: &[u8], match_score: i32, mismatch_score: i32, gap_score: i32, reversed: bool) -> Vec<i32> {
+pub fn nw_score<S: Scoring + ?Sized>(seq1: &[u8], seq2: &[u8], scoring: &S, reversed: bool) -> Vec<i32> {
+    let gap_score = scoring.gap_score();
     let mut prev_row: Vec<i32> = (0..seq2.len() + 1).map(|i| i as i32 * gap_score).collect();
     for row in 1..=seq1.len() {
         let mut current_row = vec![row as i32 * gap_score];
@@ -19,7 +25,7 @@ pub fn nw_score(seq1: &[u8], seq2: &[u8], match_score: i32, mismatch_score: i32,
                 seq2[col - 1]
             };
 
-            let diag_score = prev_row[col - 1] + if current_seq1_char == current_seq2_char { match_score } else { mismatch_score };
+            let diag_score = prev_row[col - 1] + scoring.substitution_score(current_seq1_char, current_seq2_char);
             current_row.push(
                 max(
                     max(diag_score, current_row[col - 1] + gap_score),
@@ -37,22 +43,22 @@ pub fn nw_score(seq1: &[u8], seq2: &[u8], match_score: i32, mismatch_score: i32,
 }
 
 /// Execute the Hirschberg algorithm for global alignment on seq1 and seq2 using the provided scores
-pub fn hirschberg(seq1: &[u8], seq2: &[u8], match_score: i32, mismatch_score: i32, gap_score: i32) -> Result<(Vec<u8>, Vec<u8>, Vec<u8>), Box<dyn Error>> {
+pub fn hirschberg<S: Scoring + ?Sized>(seq1: &[u8], seq2: &[u8], scoring: &S) -> Result<Alignment, Box<dyn Error>> {
     if seq1.len() <= 1 || seq2.len() <= 1 {
-        let matrix = construct_matrix(&seq1, &seq2, match_score, mismatch_score, gap_score);
-        return Ok(backtrack_alignment(&matrix, seq1, seq2, gap_score));
+        let matrix = construct_matrix(seq1, seq2, scoring);
+        return Ok(backtrack_alignment(&matrix, seq1, seq2, scoring));
     }
 
     let xmid = seq1.len() / 2;
 
-    let score_l = nw_score(&seq1[..xmid], &seq2, match_score, mismatch_score, gap_score, false);
-    let score_r = nw_score(&seq1[xmid..], &seq2, match_score, mismatch_score, gap_score, true);
+    let score_l = nw_score(&seq1[..xmid], seq2, scoring, false);
+    let score_r = nw_score(&seq1[xmid..], seq2, scoring, true);
 
     let total_score = score_l.iter().zip(score_r).map(|(&l, r)| l + r).collect::<Vec<i32>>();
     let ymid = total_score.iter().enumerate().max_by_key(|(_, &key)| key).map(|(i, _)| i).ok_or("Score L and Score R are empty, no argmax can be found")?;
 
-    let (mut alx1, mut diff1, mut aly1) = hirschberg(&seq1[..xmid], &seq2[..ymid], match_score, mismatch_score, gap_score)?;
-    let (alx2, diff2, aly2) = hirschberg(&seq1[xmid..], &seq2[ymid..], match_score, mismatch_score, gap_score)?;
+    let (mut alx1, mut diff1, mut aly1) = hirschberg(&seq1[..xmid], &seq2[..ymid], scoring)?;
+    let (alx2, diff2, aly2) = hirschberg(&seq1[xmid..], &seq2[ymid..], scoring)?;
 
     alx1.extend(alx2);
     diff1.extend(diff2);
@@ -65,7 +71,9 @@ pub fn hirschberg(seq1: &[u8], seq2: &[u8], match_score: i32, mismatch_score: i3
 mod tests {
     use std::error::Error;
     use fasta_reader::read_fasta;
-    use crate::nw_score;
+    use needleman_wunsch::{construct_matrix, score_alignment};
+    use scoring::{SimpleScoring, SubstitutionMatrix};
+    use crate::{hirschberg, nw_score};
 
     #[test]
     fn test_nw_score() -> Result<(), Box<dyn Error>> {
@@ -73,7 +81,7 @@ mod tests {
         let seq1_chars = seq1.into_bytes();
         let seq2_chars = seq2.into_bytes();
 
-        let res = nw_score(&seq1_chars, &seq2_chars, 1, -1, -1, false);
+        let res = nw_score(&seq1_chars, &seq2_chars, &SimpleScoring::new(1, -1, -1), false);
         assert_eq!(res, vec![-7, -5, -3, -1, -2, -2, 0, 0]);
         Ok(())
     }
@@ -85,8 +93,23 @@ mod tests {
         let seq1_chars = seq1.into_bytes();
         let seq2_chars = seq2.into_bytes();
 
-        let res = nw_score(&seq1_chars, &seq2_chars, 1, -1, -1, true);
+        let res = nw_score(&seq1_chars, &seq2_chars, &SimpleScoring::new(1, -1, -1), true);
         assert_eq!(res, vec![0, -1, -1, -3, -3, -5, -7, -7]);
         Ok(())
     }
+
+    #[test]
+    fn test_hirschberg_substitution_matrix() -> Result<(), Box<dyn Error>> {
+        let (seq1, seq2) = read_fasta("../tests/protein_input.fasta")?;
+        let seq1_chars = seq1.into_bytes();
+        let seq2_chars = seq2.into_bytes();
+        let scoring = SubstitutionMatrix::blosum62(-4);
+
+        let (aligned_seq1, _, aligned_seq2) = hirschberg(&seq1_chars, &seq2_chars, &scoring)?;
+        let alignment_score = score_alignment(&aligned_seq1, &aligned_seq2, &scoring);
+
+        let matrix = construct_matrix(&seq1_chars, &seq2_chars, &scoring);
+        assert_eq!(alignment_score, matrix[(seq2_chars.len(), seq1_chars.len())]);
+        Ok(())
+    }
 }
\ No newline at end of file
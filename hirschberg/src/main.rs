@@ -1,15 +1,35 @@
 use std::error::Error;
+use std::io;
 use std::str::from_utf8;
-use clap::Parser;
-use fasta_reader::read_fasta;
-use hirschberg::hirschberg;
+use clap::{Parser, ValueEnum};
+use fasta_reader::{read_fasta, read_fasta_regions, read_records};
+use hirschberg::{hirschberg, myers_miller};
+use needleman_wunsch::{score_alignment, Cigar, ReadAlignment, SamWriter};
+use scoring::{Scoring, SimpleScoring, SubstitutionMatrix};
+
+/// How the alignment is printed
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    /// The aligned sequences, with a line marking matches (`|`) and mismatches (`*`) in between
+    Alignment,
+    /// An extended CIGAR string, with `=` for matches and `X` for mismatches (sequence 1 is the reference)
+    Cigar,
+    /// A classic CIGAR string, with `M` for both matches and mismatches (sequence 1 is the reference)
+    ClassicCigar,
+    /// A SAM file with the second record as read aligned to the first record as reference
+    Sam,
+}
 
 #[derive(Parser, Debug)]
 #[clap(allow_negative_numbers = true)]
 struct Args {
-    /// The input file name
+    /// The input file name (FASTA or FASTQ)
     #[clap(short, long)]
     filename: String,
+    /// Fetch the 2 sequences to align from the indexed input file instead of using the first 2 records.
+    /// Each region is written as `name`, `name:start` or `name:start-end` (1-based, inclusive), e.g. `--region chr1:1000-2000 read1`
+    #[clap(short, long, num_args = 2, value_names = ["REGION1", "REGION2"])]
+    region: Option<Vec<String>>,
     /// The score used when there is a match
     #[clap(short, long, default_value_t = 1)]
     match_score: i32,
@@ -19,21 +39,61 @@ struct Args {
     /// The score used when there is a gap
     #[clap(short, long, default_value_t = - 3)]
     gap_score: i32,
+    /// The substitution matrix used instead of the match and mismatch score:
+    /// a built-in matrix (BLOSUM62, PAM40, PAM120 or PAM250) or the path to a matrix file in the NCBI format
+    #[clap(long)]
+    matrix: Option<String>,
+    /// Use affine gaps (Myers-Miller): the extra score for opening a gap, on top of the gap score for every character in the gap
+    #[clap(short = 'o', long)]
+    gap_open_score: Option<i32>,
+    /// How the alignment is printed
+    #[clap(long, value_enum, default_value_t = Format::Alignment)]
+    format: Format,
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
-    let Args { filename, match_score, mismatch_score, gap_score } = args;
-    let (seq1, seq2) = read_fasta(&filename)?;
+    let Args { filename, region, match_score, mismatch_score, gap_score, matrix, gap_open_score, format } = args;
+    if format == Format::Sam && region.is_some() {
+        return Err("SAM output is only supported for the first 2 records, without --region".into());
+    }
+    let (seq1, seq2) = match region {
+        Some(regions) => read_fasta_regions(&filename, &regions[0], &regions[1])?,
+        None => read_fasta(&filename)?,
+    };
     let seq1_chars = seq1.into_bytes();
     let seq2_chars = seq2.into_bytes();
 
-    let (aligned_seq1, diff_line, aligned_seq2) = hirschberg(&seq1_chars, &seq2_chars, match_score, mismatch_score, gap_score)?;
+    let scoring: Box<dyn Scoring> = match matrix {
+        Some(matrix) => Box::new(SubstitutionMatrix::from_name_or_file(&matrix, gap_score)?.with_gap_open_score(gap_open_score.unwrap_or(0))),
+        None => Box::new(SimpleScoring::new(match_score, mismatch_score, gap_score).with_gap_open_score(gap_open_score.unwrap_or(0))),
+    };
+    let (aligned_seq1, diff_line, aligned_seq2) = if gap_open_score.is_some() {
+        myers_miller(&seq1_chars, &seq2_chars, scoring.as_ref())
+    } else {
+        hirschberg(&seq1_chars, &seq2_chars, scoring.as_ref())?
+    };
 
-    println!("Aligned sequences:");
-    println!("{}", from_utf8(&*aligned_seq1)?);
-    println!("{}", from_utf8(&*diff_line)?);
-    println!("{}", from_utf8(&*aligned_seq2)?);
+    match format {
+        Format::Alignment => {
+            println!("Aligned sequences:");
+            println!("{}", from_utf8(&aligned_seq1)?);
+            println!("{}", from_utf8(&diff_line)?);
+            println!("{}", from_utf8(&aligned_seq2)?);
+        }
+        Format::Cigar | Format::ClassicCigar => {
+            let cigar = Cigar::from_alignment(&(aligned_seq1, diff_line, aligned_seq2));
+            println!("CIGAR:");
+            println!("{}", if format == Format::Cigar { cigar } else { cigar.to_classic() });
+        }
+        Format::Sam => {
+            // the records are read again for their names and quality strings
+            let records = read_records(&filename, 2)?;
+            let score = score_alignment(&aligned_seq1, &aligned_seq2, scoring.as_ref());
+            let mut writer = SamWriter::new(io::stdout().lock(), &[&records[0]], "hirschberg")?;
+            writer.write_record(&records[1], &records[0], &ReadAlignment::from_alignment(&(aligned_seq1, diff_line, aligned_seq2), score))?;
+        }
+    }
 
     Ok(())
 }
@@ -0,0 +1,206 @@
+use std::cmp::max;
+
+use needleman_wunsch::{Alignment, AlignmentBuilder};
+use scoring::Scoring;
+
+/// Score of a gap of length `length` that has to be opened
+fn gap<S: Scoring + ?Sized>(length: usize, scoring: &S) -> i32 {
+    if length == 0 { 0 } else { scoring.gap_open_score() + length as i32 * scoring.gap_score() }
+}
+
+/// Calculate the last row of the Gotoh matrices for seq1 (along the rows) against seq2 using only linear space.
+/// Returns the best scores of all alignments of seq1 with each prefix of seq2 (`cc`),
+/// and the best scores of those alignments that end with a character of seq1 aligned to a gap (`dd`).
+/// `open_score` is the gap open score of a gap of seq1 characters that touches the start of the alignment,
+/// which is 0 when that gap continues a gap of the preceding part of the alignment.
+/// If `reversed` is set, the suffixes of both sequences are aligned instead, so that index j of the result belongs to `seq2[j..]`.
+fn affine_nw_score<S: Scoring + ?Sized>(seq1: &[u8], seq2: &[u8], scoring: &S, open_score: i32, reversed: bool) -> (Vec<i32>, Vec<i32>) {
+    let gap_score = scoring.gap_score();
+    let gap_open_score = scoring.gap_open_score();
+
+    // the first row can only contain gaps in seq1, so `dd` is never better than opening a new gap
+    let mut cc: Vec<i32> = (0..=seq2.len()).map(|col| gap(col, scoring)).collect();
+    let mut dd: Vec<i32> = cc.iter().map(|&score| score + gap_open_score).collect();
+
+    let mut first_col = open_score;
+    for row in 1..=seq1.len() {
+        let current_seq1_char = if reversed { seq1[seq1.len() - row] } else { seq1[row - 1] };
+        let mut diag = cc[0];
+        first_col += gap_score;
+        cc[0] = first_col;
+        let mut current = first_col;
+        // best score of an alignment ending with a character of seq2 aligned to a gap
+        let mut insertion = first_col + gap_open_score;
+        for col in 1..=seq2.len() {
+            let current_seq2_char = if reversed { seq2[seq2.len() - col] } else { seq2[col - 1] };
+            insertion = max(insertion, current + gap_open_score) + gap_score;
+            dd[col] = max(dd[col], cc[col] + gap_open_score) + gap_score;
+            current = max(max(dd[col], insertion), diag + scoring.substitution_score(current_seq1_char, current_seq2_char));
+            diag = cc[col];
+            cc[col] = current;
+        }
+    }
+    dd[0] = cc[0];
+
+    // reverse the result if we should have been working reversed, and return
+    if reversed {
+        cc.reverse();
+        dd.reverse();
+    }
+    (cc, dd)
+}
+
+/// Align the characters of seq1 to gaps
+fn delete(alignment: &mut AlignmentBuilder, chars: &[u8]) {
+    for &char1 in chars {
+        alignment.push_gap_in_seq2(char1);
+    }
+}
+
+/// Align the characters of seq2 to gaps
+fn insert(alignment: &mut AlignmentBuilder, chars: &[u8]) {
+    for &char2 in chars {
+        alignment.push_gap_in_seq1(char2);
+    }
+}
+
+/// Recursive step of Myers-Miller, where `open_start` and `open_end` are the open scores of a gap of seq1 characters
+/// touching the start or the end of this part of the alignment
+fn myers_miller_rec<S: Scoring + ?Sized>(seq1: &[u8], seq2: &[u8], scoring: &S, open_start: i32, open_end: i32, alignment: &mut AlignmentBuilder) {
+    if seq2.is_empty() {
+        delete(alignment, seq1);
+        return;
+    }
+    if seq1.is_empty() {
+        insert(alignment, seq2);
+        return;
+    }
+
+    let gap_score = scoring.gap_score();
+    let gap_open_score = scoring.gap_open_score();
+
+    if seq1.len() == 1 {
+        // either align the single character of seq1 to a gap, at the side where that gap is cheapest,
+        let mut best_score = max(open_start, open_end) + gap_score + gap(seq2.len(), scoring);
+        let mut best_col = None;
+        // or align it with one of the characters of seq2
+        for (col, &char2) in seq2.iter().enumerate() {
+            let score = gap(col, scoring) + scoring.substitution_score(seq1[0], char2) + gap(seq2.len() - col - 1, scoring);
+            if score > best_score {
+                best_score = score;
+                best_col = Some(col);
+            }
+        }
+
+        match best_col {
+            Some(col) => {
+                insert(alignment, &seq2[..col]);
+                alignment.push_aligned(seq1[0], seq2[col]);
+                insert(alignment, &seq2[col + 1..]);
+            }
+            None if open_start >= open_end => {
+                delete(alignment, seq1);
+                insert(alignment, seq2);
+            }
+            None => {
+                insert(alignment, seq2);
+                delete(alignment, seq1);
+            }
+        }
+        return;
+    }
+
+    let xmid = seq1.len() / 2;
+    let (cc, dd) = affine_nw_score(&seq1[..xmid], seq2, scoring, open_start, false);
+    let (rr, ss) = affine_nw_score(&seq1[xmid..], seq2, scoring, open_end, true);
+
+    // the optimal alignment either crosses the middle row at (xmid, ymid) (type 1),
+    // or with a gap of seq1 characters that covers seq1[xmid - 1] and seq1[xmid] in column ymid (type 2), which is opened only once
+    let mut best_score = cc[0] + rr[0];
+    let mut ymid = 0;
+    let mut crossing_gap = false;
+    for col in 0..=seq2.len() {
+        if cc[col] + rr[col] > best_score {
+            best_score = cc[col] + rr[col];
+            ymid = col;
+            crossing_gap = false;
+        }
+        if dd[col] + ss[col] - gap_open_score > best_score {
+            best_score = dd[col] + ss[col] - gap_open_score;
+            ymid = col;
+            crossing_gap = true;
+        }
+    }
+
+    if crossing_gap {
+        myers_miller_rec(&seq1[..xmid - 1], &seq2[..ymid], scoring, open_start, 0, alignment);
+        delete(alignment, &seq1[xmid - 1..=xmid]);
+        myers_miller_rec(&seq1[xmid + 1..], &seq2[ymid..], scoring, 0, open_end, alignment);
+    } else {
+        myers_miller_rec(&seq1[..xmid], &seq2[..ymid], scoring, open_start, gap_open_score, alignment);
+        myers_miller_rec(&seq1[xmid..], &seq2[ymid..], scoring, gap_open_score, open_end, alignment);
+    }
+}
+
+/// Execute the Myers-Miller algorithm: global alignment with affine gaps in linear space, using the divide and conquer approach of Hirschberg.
+/// A gap of length k scores `gap_open_score + k * gap_score`, which results in the same score as the Gotoh algorithm.
+pub fn myers_miller<S: Scoring + ?Sized>(seq1: &[u8], seq2: &[u8], scoring: &S) -> Alignment {
+    // the recursion finds the columns from start to end
+    let mut alignment = AlignmentBuilder::forward_with_capacity(seq1.len() + seq2.len());
+    myers_miller_rec(seq1, seq2, scoring, scoring.gap_open_score(), scoring.gap_open_score(), &mut alignment);
+
+    alignment.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use fasta_reader::read_fasta;
+    use needleman_wunsch::{construct_affine_matrices, score_alignment};
+    use scoring::{SimpleScoring, SubstitutionMatrix};
+
+    use crate::myers_miller::myers_miller;
+
+    #[test]
+    fn test_myers_miller_equals_gotoh() -> Result<(), Box<dyn Error>> {
+        let (seq1, seq2) = read_fasta("../tests/verylarge.fasta")?;
+        let seq1_chars = seq1.into_bytes();
+        let seq2_chars = seq2.into_bytes();
+
+        for scoring in [SimpleScoring::new(1, -1, -1).with_gap_open_score(-4), SimpleScoring::new(2, -3, -1).with_gap_open_score(-10), SimpleScoring::new(1, -1, -3)] {
+            let (aligned_seq1, diff_line, aligned_seq2) = myers_miller(&seq1_chars, &seq2_chars, &scoring);
+            assert_eq!(aligned_seq1.len(), diff_line.len());
+            assert_eq!(aligned_seq1.iter().filter(|&&c| c != b'-').copied().collect::<Vec<u8>>(), seq1_chars);
+            assert_eq!(aligned_seq2.iter().filter(|&&c| c != b'-').copied().collect::<Vec<u8>>(), seq2_chars);
+
+            let matrices = construct_affine_matrices(&seq1_chars, &seq2_chars, &scoring);
+            assert_eq!(score_alignment(&aligned_seq1, &aligned_seq2, &scoring), matrices.score());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_myers_miller_protein() -> Result<(), Box<dyn Error>> {
+        let (seq1, seq2) = read_fasta("../tests/protein_input.fasta")?;
+        let seq1_chars = seq1.into_bytes();
+        let seq2_chars = seq2.into_bytes();
+        let scoring = SubstitutionMatrix::blosum62(-1).with_gap_open_score(-10);
+
+        let (aligned_seq1, _, aligned_seq2) = myers_miller(&seq1_chars, &seq2_chars, &scoring);
+        let matrices = construct_affine_matrices(&seq1_chars, &seq2_chars, &scoring);
+        assert_eq!(score_alignment(&aligned_seq1, &aligned_seq2, &scoring), matrices.score());
+        Ok(())
+    }
+
+    #[test]
+    fn test_myers_miller_small_inputs() {
+        let scoring = SimpleScoring::new(1, -1, -1).with_gap_open_score(-3);
+        let inputs: [(&[u8], &[u8]); 6] = [(b"", b""), (b"A", b""), (b"", b"ACG"), (b"A", b"CGT"), (b"ACGT", b"A"), (b"AAAATTTT", b"AAAACCCCTTTT")];
+        for (seq1, seq2) in inputs {
+            let (aligned_seq1, _, aligned_seq2) = myers_miller(seq1, seq2, &scoring);
+            let matrices = construct_affine_matrices(seq1, seq2, &scoring);
+            assert_eq!(score_alignment(&aligned_seq1, &aligned_seq2, &scoring), matrices.score());
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use crate::Alignment;
+
+/// The 3 lines of an alignment while it is built column by column.
+/// A traceback walks from the end of the alignment to the start, so `with_capacity` builds back to front:
+/// the columns are added at the end and reversed once by `finish`, since inserting every column at the front takes quadratic time.
+/// Aligners that find the columns from start to end (e.g. divide and conquer) use `forward_with_capacity` instead.
+pub struct AlignmentBuilder {
+    aligned_seq1: Vec<u8>,
+    diff_line: Vec<u8>,
+    aligned_seq2: Vec<u8>,
+    back_to_front: bool,
+}
+
+impl AlignmentBuilder {
+    /// An empty alignment that is built back to front, with room for `capacity` columns:
+    /// an alignment of seq1 and seq2 is at most `seq1.len() + seq2.len()` long
+    pub fn with_capacity(capacity: usize) -> AlignmentBuilder {
+        AlignmentBuilder::new(capacity, true)
+    }
+
+    /// An empty alignment that is built from start to end, with room for `capacity` columns
+    pub fn forward_with_capacity(capacity: usize) -> AlignmentBuilder {
+        AlignmentBuilder::new(capacity, false)
+    }
+
+    fn new(capacity: usize, back_to_front: bool) -> AlignmentBuilder {
+        AlignmentBuilder { aligned_seq1: Vec::with_capacity(capacity), diff_line: Vec::with_capacity(capacity), aligned_seq2: Vec::with_capacity(capacity), back_to_front }
+    }
+
+    /// Add the next column, with a character of seq1 aligned to a gap in seq2
+    pub fn push_gap_in_seq2(&mut self, char1: u8) {
+        self.aligned_seq1.push(char1);
+        self.diff_line.push(b' ');
+        self.aligned_seq2.push(b'-');
+    }
+
+    /// Add the next column, with a character of seq2 aligned to a gap in seq1
+    pub fn push_gap_in_seq1(&mut self, char2: u8) {
+        self.aligned_seq1.push(b'-');
+        self.diff_line.push(b' ');
+        self.aligned_seq2.push(char2);
+    }
+
+    /// Add the next column, with a character of seq1 aligned to a character of seq2, marked as a match (`|`) or mismatch (`*`)
+    pub fn push_aligned(&mut self, char1: u8, char2: u8) {
+        self.aligned_seq1.push(char1);
+        self.diff_line.push(if char1 == char2 { b'|' } else { b'*' });
+        self.aligned_seq2.push(char2);
+    }
+
+    /// The alignment from start to end
+    pub fn finish(mut self) -> Alignment {
+        if self.back_to_front {
+            self.aligned_seq1.reverse();
+            self.diff_line.reverse();
+            self.aligned_seq2.reverse();
+        }
+        (self.aligned_seq1, self.diff_line, self.aligned_seq2)
+    }
+}
@@ -0,0 +1,255 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use crate::{Alignment, CigarError};
+
+/// An operation of a CIGAR string, where seq1 is the reference and seq2 the query, as in SAM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CigarOp {
+    /// `M`: aligned characters, which can match or mismatch
+    AlignmentMatch,
+    /// `=`: aligned characters that match
+    SequenceMatch,
+    /// `X`: aligned characters that mismatch
+    SequenceMismatch,
+    /// `I`: a character of seq2 aligned to a gap in seq1
+    Insertion,
+    /// `D`: a character of seq1 aligned to a gap in seq2
+    Deletion,
+}
+
+impl CigarOp {
+    pub fn symbol(self) -> char {
+        match self {
+            CigarOp::AlignmentMatch => 'M',
+            CigarOp::SequenceMatch => '=',
+            CigarOp::SequenceMismatch => 'X',
+            CigarOp::Insertion => 'I',
+            CigarOp::Deletion => 'D',
+        }
+    }
+
+    fn from_symbol(symbol: char) -> Option<CigarOp> {
+        match symbol {
+            'M' => Some(CigarOp::AlignmentMatch),
+            '=' => Some(CigarOp::SequenceMatch),
+            'X' => Some(CigarOp::SequenceMismatch),
+            'I' => Some(CigarOp::Insertion),
+            'D' => Some(CigarOp::Deletion),
+            _ => None,
+        }
+    }
+
+    /// Whether the operation consumes a character of seq1 and of seq2
+    fn consumes(self) -> (bool, bool) {
+        match self {
+            CigarOp::Insertion => (false, true),
+            CigarOp::Deletion => (true, false),
+            _ => (true, true),
+        }
+    }
+}
+
+/// An alignment as a list of run-length encoded operations, which is written as a CIGAR string such as `3=1X2I`.
+/// The empty alignment is written as `*`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Cigar {
+    operations: Vec<(usize, CigarOp)>,
+}
+
+impl Cigar {
+    /// The extended CIGAR of an alignment, which tells matches (`=`) and mismatches (`X`) apart
+    pub fn from_alignment(alignment: &Alignment) -> Cigar {
+        let (aligned_seq1, _, aligned_seq2) = alignment;
+        let mut cigar = Cigar::default();
+        for (&char1, &char2) in aligned_seq1.iter().zip(aligned_seq2) {
+            let operation = match (char1, char2) {
+                (b'-', _) => CigarOp::Insertion,
+                (_, b'-') => CigarOp::Deletion,
+                _ if char1 == char2 => CigarOp::SequenceMatch,
+                _ => CigarOp::SequenceMismatch,
+            };
+            cigar.push(1, operation);
+        }
+        cigar
+    }
+
+    /// The run-length encoded operations, as (length, operation)
+    pub fn operations(&self) -> &[(usize, CigarOp)] {
+        &self.operations
+    }
+
+    /// The classic CIGAR, where matches and mismatches are both written as `M`
+    pub fn to_classic(&self) -> Cigar {
+        let mut cigar = Cigar::default();
+        for &(length, operation) in &self.operations {
+            let operation = match operation {
+                CigarOp::SequenceMatch | CigarOp::SequenceMismatch => CigarOp::AlignmentMatch,
+                _ => operation,
+            };
+            cigar.push(length, operation);
+        }
+        cigar
+    }
+
+    /// Add `length` times `operation` at the end, merging it with the last operation if they are the same
+    fn push(&mut self, length: usize, operation: CigarOp) {
+        match self.operations.last_mut() {
+            Some((last_length, last_operation)) if *last_operation == operation => *last_length += length,
+            _ => self.operations.push((length, operation)),
+        }
+    }
+
+    /// Rebuild the alignment of seq1 and seq2 that this CIGAR describes, with the same diff line as the aligners
+    pub fn to_alignment(&self, seq1: &[u8], seq2: &[u8]) -> Result<Alignment, CigarError> {
+        let (seq1_length, seq2_length) = self.operations.iter().fold((0usize, 0usize), |(seq1_length, seq2_length), &(length, operation)| {
+            let (consumes_seq1, consumes_seq2) = operation.consumes();
+            // saturate, so lengths that can't be right are reported as a mismatch instead of overflowing
+            (seq1_length.saturating_add(if consumes_seq1 { length } else { 0 }), seq2_length.saturating_add(if consumes_seq2 { length } else { 0 }))
+        });
+        if seq1_length != seq1.len() {
+            return Err(CigarError::LengthMismatch { sequence: 1, cigar_length: seq1_length, sequence_length: seq1.len() });
+        }
+        if seq2_length != seq2.len() {
+            return Err(CigarError::LengthMismatch { sequence: 2, cigar_length: seq2_length, sequence_length: seq2.len() });
+        }
+
+        let alignment_length = self.operations.iter().map(|&(length, _)| length).sum();
+        let mut aligned_seq1: Vec<u8> = Vec::with_capacity(alignment_length);
+        let mut aligned_seq2: Vec<u8> = Vec::with_capacity(alignment_length);
+        let mut diff_line: Vec<u8> = Vec::with_capacity(alignment_length);
+        let (mut seq1_chars, mut seq2_chars) = (seq1.iter(), seq2.iter());
+        for &(length, operation) in &self.operations {
+            for _ in 0..length {
+                match operation {
+                    CigarOp::Insertion => {
+                        aligned_seq1.push(b'-');
+                        diff_line.push(b' ');
+                        aligned_seq2.push(*seq2_chars.next().expect("The length of seq2 is checked"));
+                    }
+                    CigarOp::Deletion => {
+                        aligned_seq1.push(*seq1_chars.next().expect("The length of seq1 is checked"));
+                        diff_line.push(b' ');
+                        aligned_seq2.push(b'-');
+                    }
+                    _ => {
+                        let char1 = *seq1_chars.next().expect("The length of seq1 is checked");
+                        let char2 = *seq2_chars.next().expect("The length of seq2 is checked");
+                        let is_match = char1 == char2;
+                        if (operation == CigarOp::SequenceMatch && !is_match) || (operation == CigarOp::SequenceMismatch && is_match) {
+                            return Err(CigarError::WrongMatch { column: aligned_seq1.len() + 1 });
+                        }
+                        aligned_seq1.push(char1);
+                        diff_line.push(if is_match { b'|' } else { b'*' });
+                        aligned_seq2.push(char2);
+                    }
+                }
+            }
+        }
+        Ok((aligned_seq1, diff_line, aligned_seq2))
+    }
+}
+
+impl Display for Cigar {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.operations.is_empty() {
+            return write!(f, "*");
+        }
+        for &(length, operation) in &self.operations {
+            write!(f, "{}{}", length, operation.symbol())?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Cigar {
+    type Err = CigarError;
+
+    /// Parse an extended or classic CIGAR string, where adjacent equal operations are merged
+    fn from_str(cigar: &str) -> Result<Cigar, CigarError> {
+        let mut result = Cigar::default();
+        if cigar == "*" {
+            return Ok(result);
+        }
+
+        let mut length: usize = 0;
+        let mut has_length = false;
+        for (index, symbol) in cigar.chars().enumerate() {
+            if let Some(digit) = symbol.to_digit(10) {
+                length = length.checked_mul(10).and_then(|length| length.checked_add(digit as usize)).ok_or(CigarError::LengthOverflow { position: index + 1 })?;
+                has_length = true;
+                continue;
+            }
+            let operation = CigarOp::from_symbol(symbol).ok_or(CigarError::InvalidOperation { operation: symbol, position: index + 1 })?;
+            if length == 0 {
+                return Err(CigarError::MissingLength { position: index + 1 });
+            }
+            // the merged length of adjacent equal operations can overflow as well
+            if result.operations.last().is_some_and(|&(last_length, last_operation)| last_operation == operation && last_length.checked_add(length).is_none()) {
+                return Err(CigarError::LengthOverflow { position: index + 1 });
+            }
+            result.push(length, operation);
+            length = 0;
+            has_length = false;
+        }
+        if has_length {
+            return Err(CigarError::MissingOperation);
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fasta_reader::read_fasta;
+    use scoring::SimpleScoring;
+
+    use crate::{backtrack_alignment, construct_matrix, Cigar, CigarError, CigarOp};
+
+    #[test]
+    fn test_cigar() {
+        let alignment = (b"GA-TTACA".to_vec(), b"|* | *| ".to_vec(), b"GCAT-GC-".to_vec());
+        let cigar = Cigar::from_alignment(&alignment);
+        assert_eq!(cigar.to_string(), "1=1X1I1=1D1X1=1D");
+        assert_eq!(cigar.to_classic().to_string(), "2M1I1M1D2M1D");
+        assert_eq!(cigar.operations()[2], (1, CigarOp::Insertion));
+
+        let cigar = Cigar::from_alignment(&(b"AAC-".to_vec(), b"||  ".to_vec(), b"AA-T".to_vec()));
+        assert_eq!(cigar.to_string(), "2=1D1I");
+        assert_eq!(Cigar::default().to_string(), "*");
+    }
+
+    #[test]
+    fn test_cigar_to_alignment() -> Result<(), Box<dyn std::error::Error>> {
+        let (seq1, seq2) = read_fasta("../tests/input.fasta")?;
+        let (seq1, seq2) = (seq1.into_bytes(), seq2.into_bytes());
+        let scoring = SimpleScoring::new(1, -1, -1);
+        let alignment = backtrack_alignment(&construct_matrix(&seq1, &seq2, &scoring), &seq1, &seq2, &scoring);
+
+        // both the extended and the classic CIGAR give back the same alignment
+        let cigar = Cigar::from_alignment(&alignment);
+        assert_eq!(cigar.to_string().parse::<Cigar>()?.to_alignment(&seq1, &seq2)?, alignment);
+        assert_eq!(cigar.to_classic().to_string().parse::<Cigar>()?.to_alignment(&seq1, &seq2)?, alignment);
+        assert_eq!("*".parse::<Cigar>()?.to_alignment(b"", b"")?, (vec![], vec![], vec![]));
+        assert_eq!("1M1M".parse::<Cigar>()?.operations(), &[(2, CigarOp::AlignmentMatch)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cigar_errors() {
+        assert_eq!("3M2S".parse::<Cigar>(), Err(CigarError::InvalidOperation { operation: 'S', position: 4 }));
+        assert_eq!("3MD".parse::<Cigar>(), Err(CigarError::MissingLength { position: 3 }));
+        assert_eq!("0M".parse::<Cigar>(), Err(CigarError::MissingLength { position: 2 }));
+        assert_eq!("3M2".parse::<Cigar>(), Err(CigarError::MissingOperation));
+        assert_eq!("99999999999999999999M".parse::<Cigar>(), Err(CigarError::LengthOverflow { position: 20 }));
+        assert_eq!(format!("{}M{}M", usize::MAX, usize::MAX).parse::<Cigar>(), Err(CigarError::LengthOverflow { position: 2 * usize::MAX.to_string().len() + 2 }));
+
+        let cigar: Cigar = "2=1I".parse().unwrap();
+        assert_eq!(cigar.to_alignment(b"AC", b"AC"), Err(CigarError::LengthMismatch { sequence: 2, cigar_length: 3, sequence_length: 2 }));
+        assert_eq!(cigar.to_alignment(b"ACG", b"ACG"), Err(CigarError::LengthMismatch { sequence: 1, cigar_length: 2, sequence_length: 3 }));
+        assert_eq!(cigar.to_alignment(b"AC", b"AGT"), Err(CigarError::WrongMatch { column: 2 }));
+        let cigar: Cigar = format!("{}M{}D", usize::MAX, usize::MAX).parse().unwrap();
+        assert_eq!(cigar.to_alignment(b"A", b"A"), Err(CigarError::LengthMismatch { sequence: 1, cigar_length: usize::MAX, sequence_length: 1 }));
+        assert_eq!("1X".parse::<Cigar>().unwrap().to_alignment(b"A", b"A"), Err(CigarError::WrongMatch { column: 1 }));
+    }
+}
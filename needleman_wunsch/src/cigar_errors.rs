@@ -0,0 +1,37 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+/// Error indicating that a CIGAR string can't be parsed, or does not describe an alignment of the given sequences.
+/// All positions are 1-based.
+#[derive(Debug, PartialEq, Eq)]
+pub enum CigarError {
+    /// The character at the given position is not one of the operations `M`, `=`, `X`, `I` or `D`
+    InvalidOperation { operation: char, position: usize },
+    /// The operation at the given position is not preceded by a length of at least 1
+    MissingLength { position: usize },
+    /// The length of the operation at the given position does not fit in a `usize`
+    LengthOverflow { position: usize },
+    /// The CIGAR string ends with a length that is not followed by an operation
+    MissingOperation,
+    /// The CIGAR string consumes a different number of characters of sequence 1 or 2 than the sequence contains
+    LengthMismatch { sequence: usize, cigar_length: usize, sequence_length: usize },
+    /// A `=` operation covers different characters, or an `X` operation covers equal characters, in the given column of the alignment
+    WrongMatch { column: usize },
+}
+
+impl Display for CigarError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CigarError::InvalidOperation { operation, position } => write!(f, "Position {} of the CIGAR string: invalid operation '{}'", position, operation.escape_default()),
+            CigarError::MissingLength { position } => write!(f, "Position {} of the CIGAR string: expected a length of at least 1 before the operation", position),
+            CigarError::LengthOverflow { position } => write!(f, "Position {} of the CIGAR string: the length is too large", position),
+            CigarError::MissingOperation => write!(f, "The CIGAR string ends with a length without an operation"),
+            CigarError::LengthMismatch { sequence, cigar_length, sequence_length } => {
+                write!(f, "The CIGAR string covers {} characters of sequence {}, but the sequence has length {}", cigar_length, sequence, sequence_length)
+            }
+            CigarError::WrongMatch { column } => write!(f, "Column {} of the alignment does not match its CIGAR operation", column),
+        }
+    }
+}
+
+impl Error for CigarError {}
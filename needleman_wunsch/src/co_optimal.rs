@@ -0,0 +1,223 @@
+use crate::{Alignment, AlignmentBuilder, DpMatrix, TRACE_DIAG, TRACE_LEFT, TRACE_UP};
+
+/// Which move the traceback takes when more than one move into a cell gives its optimal score.
+/// The traceback walks from the end of the alignment to the start, so taking gaps as late as possible places them at the right.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TieBreak {
+    /// Prefer a gap in seq2, then a gap in seq1, then the diagonal: the gaps are shifted as far right as possible.
+    /// This is the order `backtrack_alignment` uses.
+    #[default]
+    GapsRightAligned,
+    /// Prefer the diagonal, then a gap in seq2, then a gap in seq1: the gaps are shifted as far left as possible,
+    /// as in the left-normalisation of indels
+    GapsLeftAligned,
+    /// Prefer the diagonal if its characters match, then a gap in seq2, then a gap in seq1, and a mismatch last
+    PreferMatch,
+}
+
+impl TieBreak {
+    /// The moves in the order they are tried, given whether the characters on the diagonal match
+    fn order(self, is_match: bool) -> [u8; 3] {
+        match self {
+            TieBreak::GapsRightAligned => [TRACE_LEFT, TRACE_UP, TRACE_DIAG],
+            TieBreak::GapsLeftAligned => [TRACE_DIAG, TRACE_LEFT, TRACE_UP],
+            TieBreak::PreferMatch if is_match => [TRACE_DIAG, TRACE_LEFT, TRACE_UP],
+            TieBreak::PreferMatch => [TRACE_LEFT, TRACE_UP, TRACE_DIAG],
+        }
+    }
+}
+
+/// A cell on the current traceback path, with the optimal moves into it that are not tried yet
+struct Frame {
+    row: usize,
+    col: usize,
+    remaining: u8,
+}
+
+/// Iterator over all co-optimal alignments in a direction matrix, see `co_optimal_alignments`
+pub struct CoOptimalAlignments<'a> {
+    directions: &'a DpMatrix<u8>,
+    seq1: &'a [u8],
+    seq2: &'a [u8],
+    end_cell: (usize, usize),
+    tie_break: TieBreak,
+    /// the cells of the current path from the end cell, every cell except the last one is followed by the move in `moves`
+    path: Vec<Frame>,
+    moves: Vec<u8>,
+}
+
+/// Lazily enumerate every optimal alignment that ends in `end_cell` (see `best_end_cell`), by a depth-first search
+/// through the direction matrix built by `construct_matrix_with_directions`.
+/// With free trailing gaps, more cells can have the optimal score: `all_co_optimal_alignments` also enumerates the alignments ending in those.
+/// The first alignment is the one `backtrack_alignment_from_directions` returns with the same `tie_break`,
+/// every next alignment changes the latest possible move. The number of co-optimal alignments can grow exponentially,
+/// so use `take` to cap it.
+pub fn co_optimal_alignments<'a>(directions: &'a DpMatrix<u8>, seq1: &'a [u8], seq2: &'a [u8], end_cell: (usize, usize), tie_break: TieBreak) -> CoOptimalAlignments<'a> {
+    let (row, col) = end_cell;
+    CoOptimalAlignments {
+        directions,
+        seq1,
+        seq2,
+        end_cell,
+        tie_break,
+        path: vec![Frame { row, col, remaining: directions[(row, col)] }],
+        moves: vec![],
+    }
+}
+
+/// Lazily enumerate every optimal alignment that ends in one of `end_cells` (see `best_end_cells`), one end cell after the other
+pub fn all_co_optimal_alignments<'a>(directions: &'a DpMatrix<u8>, seq1: &'a [u8], seq2: &'a [u8], end_cells: Vec<(usize, usize)>, tie_break: TieBreak) -> impl Iterator<Item = Alignment> + 'a {
+    end_cells.into_iter().flat_map(move |end_cell| co_optimal_alignments(directions, seq1, seq2, end_cell, tie_break))
+}
+
+impl CoOptimalAlignments<'_> {
+    /// The alignment of the current path, which reaches the first cell
+    fn alignment(&self) -> Alignment {
+        let (end_row, end_col) = self.end_cell;
+        let (seq1, seq2) = (self.seq1, self.seq2);
+
+        let mut alignment = AlignmentBuilder::with_capacity(seq1.len() + seq2.len());
+
+        // the trailing gaps, at most one of both loops adds characters
+        for &char1 in seq1[end_col..].iter().rev() {
+            alignment.push_gap_in_seq2(char1);
+        }
+        for &char2 in seq2[end_row..].iter().rev() {
+            alignment.push_gap_in_seq1(char2);
+        }
+
+        for (frame, &direction) in self.path.iter().zip(&self.moves) {
+            let (row, col) = (frame.row, frame.col);
+            match direction {
+                TRACE_LEFT => alignment.push_gap_in_seq2(seq1[col - 1]),
+                TRACE_UP => alignment.push_gap_in_seq1(seq2[row - 1]),
+                _ => alignment.push_aligned(seq1[col - 1], seq2[row - 1]),
+            }
+        }
+
+        alignment.finish()
+    }
+}
+
+impl Iterator for CoOptimalAlignments<'_> {
+    type Item = Alignment;
+
+    fn next(&mut self) -> Option<Alignment> {
+        loop {
+            let frame = self.path.last_mut()?;
+            if frame.row == 0 && frame.col == 0 {
+                let alignment = self.alignment();
+                // continue the search from the previous cell
+                self.path.pop();
+                self.moves.pop();
+                return Some(alignment);
+            }
+
+            let is_match = frame.row != 0 && frame.col != 0 && self.seq1[frame.col - 1] == self.seq2[frame.row - 1];
+            let Some(direction) = self.tie_break.order(is_match).into_iter().find(|&direction| frame.remaining & direction != 0) else {
+                // every move into this cell is tried
+                self.path.pop();
+                self.moves.pop();
+                continue;
+            };
+            frame.remaining &= !direction;
+
+            let (row, col) = match direction {
+                TRACE_LEFT => (frame.row, frame.col - 1),
+                TRACE_UP => (frame.row - 1, frame.col),
+                _ => (frame.row - 1, frame.col - 1),
+            };
+            self.moves.push(direction);
+            self.path.push(Frame { row, col, remaining: self.directions[(row, col)] });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use scoring::SimpleScoring;
+
+    use crate::{all_co_optimal_alignments, backtrack_alignment, backtrack_alignment_with_end_gaps, best_end_cell, best_end_cells, co_optimal_alignments, construct_matrix_with_directions, score_alignment, Alignment, EndGaps, TieBreak};
+
+    /// All co-optimal global alignments of seq1 and seq2
+    fn all_alignments(seq1: &[u8], seq2: &[u8], scoring: &SimpleScoring, tie_break: TieBreak) -> Vec<Alignment> {
+        let (matrix, directions) = construct_matrix_with_directions(seq1, seq2, scoring, EndGaps::GLOBAL);
+        co_optimal_alignments(&directions, seq1, seq2, best_end_cell(&matrix, EndGaps::GLOBAL), tie_break).collect()
+    }
+
+    #[test]
+    fn test_co_optimal_alignments() {
+        let scoring = SimpleScoring::new(1, -1, -1);
+        // the gap can be at any of the 3 positions
+        let alignments = all_alignments(b"AAA", b"AA", &scoring, TieBreak::default());
+        let expected: Vec<Alignment> = vec![
+            (b"AAA".to_vec(), b"|| ".to_vec(), b"AA-".to_vec()),
+            (b"AAA".to_vec(), b"| |".to_vec(), b"A-A".to_vec()),
+            (b"AAA".to_vec(), b" ||".to_vec(), b"-AA".to_vec()),
+        ];
+        assert_eq!(alignments, expected);
+
+        let (matrix, directions) = construct_matrix_with_directions(b"AAA", b"AA", &scoring, EndGaps::GLOBAL);
+        let mut iterator = co_optimal_alignments(&directions, b"AAA", b"AA", (2, 3), TieBreak::default());
+        assert_eq!(iterator.next(), Some(backtrack_alignment(&matrix, b"AAA", b"AA", &scoring)));
+        assert_eq!(iterator.take(1).count(), 1);
+
+        // every alignment is different and optimal, whatever the tie break
+        let (seq1, seq2) = (b"GATTACA".as_slice(), b"GCATGCU".as_slice());
+        let score = matrix_score(seq1, seq2, &scoring);
+        let alignments = all_alignments(seq1, seq2, &scoring, TieBreak::default());
+        assert!(alignments.len() > 1);
+        assert_eq!(alignments.iter().collect::<HashSet<&Alignment>>().len(), alignments.len());
+        for (aligned_seq1, _, aligned_seq2) in &alignments {
+            assert_eq!(score_alignment(aligned_seq1, aligned_seq2, &scoring), score);
+        }
+        for tie_break in [TieBreak::GapsLeftAligned, TieBreak::PreferMatch] {
+            let other_alignments = all_alignments(seq1, seq2, &scoring, tie_break);
+            assert_eq!(other_alignments.iter().collect::<HashSet<&Alignment>>(), alignments.iter().collect::<HashSet<&Alignment>>());
+        }
+
+        // an empty sequence only has 1 alignment
+        assert_eq!(all_alignments(b"", b"AC", &scoring, TieBreak::default()), vec![(b"--".to_vec(), b"  ".to_vec(), b"AC".to_vec())]);
+    }
+
+    #[test]
+    fn test_all_co_optimal_alignments() {
+        let scoring = SimpleScoring::new(1, -1, -1);
+        // with free trailing gaps in seq1, AC can end after the first or the second AC of seq1
+        let (seq1, seq2) = (b"ACAC".as_slice(), b"AC".as_slice());
+        let (matrix, directions) = construct_matrix_with_directions(seq1, seq2, &scoring, EndGaps::SEMI_GLOBAL);
+        let end_cells = best_end_cells(&matrix, EndGaps::SEMI_GLOBAL);
+        assert_eq!(end_cells, vec![(2, 4), (2, 2)]);
+        assert_eq!(end_cells[0], best_end_cell(&matrix, EndGaps::SEMI_GLOBAL));
+
+        let alignments: Vec<Alignment> = all_co_optimal_alignments(&directions, seq1, seq2, end_cells, TieBreak::default()).collect();
+        assert_eq!(alignments, vec![
+            (b"ACAC".to_vec(), b"  ||".to_vec(), b"--AC".to_vec()),
+            (b"ACAC".to_vec(), b"||  ".to_vec(), b"AC--".to_vec()),
+        ]);
+        assert_eq!(alignments[0], backtrack_alignment_with_end_gaps(&matrix, seq1, seq2, &scoring, EndGaps::SEMI_GLOBAL));
+    }
+
+    fn matrix_score(seq1: &[u8], seq2: &[u8], scoring: &SimpleScoring) -> i32 {
+        let (matrix, _) = construct_matrix_with_directions(seq1, seq2, scoring, EndGaps::GLOBAL);
+        matrix[(seq2.len(), seq1.len())]
+    }
+
+    #[test]
+    fn test_tie_break() {
+        let scoring = SimpleScoring::new(1, -1, -1);
+        let first_alignment = |seq1: &[u8], seq2: &[u8], scoring: &SimpleScoring, tie_break| all_alignments(seq1, seq2, scoring, tie_break).swap_remove(0);
+
+        // the deletion of an A in a run of A's
+        assert_eq!(first_alignment(b"CAAAG", b"CAAG", &scoring, TieBreak::GapsRightAligned).2, b"CAA-G");
+        assert_eq!(first_alignment(b"CAAAG", b"CAAG", &scoring, TieBreak::GapsLeftAligned).2, b"C-AAG");
+        assert_eq!(first_alignment(b"CAAG", b"CAAAG", &scoring, TieBreak::GapsLeftAligned).0, b"C-AAG");
+
+        // a mismatch scores the same as 2 gaps
+        let scoring = SimpleScoring::new(1, -2, -1);
+        assert_eq!(first_alignment(b"CAG", b"CTG", &scoring, TieBreak::GapsLeftAligned).1, b"|*|");
+        assert!(!first_alignment(b"CAG", b"CTG", &scoring, TieBreak::PreferMatch).1.contains(&b'*'));
+    }
+}
@@ -0,0 +1,87 @@
+use std::ops::{Index, IndexMut};
+
+/// Bit flag in a traceback direction matrix: the cell is reached optimally from the cell diagonally above
+pub const TRACE_DIAG: u8 = 1;
+/// Bit flag in a traceback direction matrix: the cell is reached optimally from the cell to its left (a gap in seq2)
+pub const TRACE_LEFT: u8 = 2;
+/// Bit flag in a traceback direction matrix: the cell is reached optimally from the cell above (a gap in seq1)
+pub const TRACE_UP: u8 = 4;
+
+/// A dynamic programming matrix stored in a single contiguous buffer, row after row.
+/// Cells are indexed as `matrix[(row, col)]`. A `DpMatrix<u8>` with `TRACE_*` flags is used as traceback direction matrix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DpMatrix<T = i32> {
+    rows: usize,
+    cols: usize,
+    cells: Vec<T>,
+}
+
+impl<T: Clone> DpMatrix<T> {
+    /// A matrix where every cell contains `value`
+    pub fn new(rows: usize, cols: usize, value: T) -> DpMatrix<T> {
+        DpMatrix { rows, cols, cells: vec![value; rows * cols] }
+    }
+}
+
+impl<T> DpMatrix<T> {
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// The cells of a single row
+    pub fn row(&self, row: usize) -> &[T] {
+        &self.cells[row * self.cols..(row + 1) * self.cols]
+    }
+
+    pub fn row_mut(&mut self, row: usize) -> &mut [T] {
+        &mut self.cells[row * self.cols..(row + 1) * self.cols]
+    }
+
+    /// Iterate over the rows, from the first to the last
+    pub fn iter_rows(&self) -> impl Iterator<Item = &[T]> {
+        (0..self.rows).map(|row| self.row(row))
+    }
+}
+
+impl<T> Index<(usize, usize)> for DpMatrix<T> {
+    type Output = T;
+
+    /// Panics if the cell lies outside the matrix, also in release builds, instead of wrapping to the next row
+    fn index(&self, (row, col): (usize, usize)) -> &T {
+        &self.row(row)[col]
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for DpMatrix<T> {
+    fn index_mut(&mut self, (row, col): (usize, usize)) -> &mut T {
+        &mut self.row_mut(row)[col]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::DpMatrix;
+
+    #[test]
+    fn test_dp_matrix() {
+        let mut matrix = DpMatrix::new(2, 3, 0);
+        matrix[(1, 2)] = 5;
+        matrix.row_mut(0).copy_from_slice(&[1, 2, 3]);
+
+        assert_eq!(matrix[(1, 2)], 5);
+        assert_eq!(matrix.row(0), &[1, 2, 3]);
+        assert_eq!(matrix.iter_rows().collect::<Vec<&[i32]>>(), vec![&[1, 2, 3], &[0, 0, 5]]);
+        assert_eq!((matrix.rows(), matrix.cols()), (2, 3));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_dp_matrix_column_out_of_bounds() {
+        let matrix = DpMatrix::new(2, 3, 0);
+        let _ = matrix[(0, 3)];
+    }
+}
@@ -0,0 +1,205 @@
+use std::cmp::max;
+
+use scoring::Scoring;
+
+use crate::{Alignment, AlignmentBuilder, DpMatrix};
+
+/// Stand-in for minus infinity that can't overflow when a few scores are added to it
+pub(crate) const NEG_INF: i32 = i32::MIN / 2;
+
+/// The 3 matrices of the Gotoh algorithm, with seq2 along the rows and seq1 along the columns
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AffineMatrices {
+    /// Best score of an alignment that ends with 2 aligned characters
+    pub m: DpMatrix,
+    /// Best score of an alignment that ends with a character of seq1 aligned to a gap (a horizontal move)
+    pub x: DpMatrix,
+    /// Best score of an alignment that ends with a character of seq2 aligned to a gap (a vertical move)
+    pub y: DpMatrix,
+}
+
+impl AffineMatrices {
+    /// The score of the optimal global alignment
+    pub fn score(&self) -> i32 {
+        let row = self.m.rows() - 1;
+        let col = self.m.cols() - 1;
+        max(max(self.m[(row, col)], self.x[(row, col)]), self.y[(row, col)])
+    }
+}
+
+/// The matrix an alignment ends in during the traceback
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    M,
+    X,
+    Y,
+}
+
+/// Fill in the Gotoh matrices for global alignment with affine gaps, where a gap of length k scores `gap_open_score + k * gap_score`
+pub fn construct_affine_matrices<S: Scoring + ?Sized>(seq1: &[u8], seq2: &[u8], scoring: &S) -> AffineMatrices {
+    let gap_score = scoring.gap_score();
+    let open_score = scoring.gap_open_score() + gap_score;
+    let mut m = DpMatrix::new(seq2.len() + 1, seq1.len() + 1, NEG_INF);
+    let mut x = DpMatrix::new(seq2.len() + 1, seq1.len() + 1, NEG_INF);
+    let mut y = DpMatrix::new(seq2.len() + 1, seq1.len() + 1, NEG_INF);
+
+    // initialize the first column and first row: only a single gap can reach them
+    m[(0, 0)] = 0;
+    for (col, x_value) in x.row_mut(0).iter_mut().enumerate().skip(1) {
+        *x_value = scoring.gap_open_score() + col as i32 * gap_score;
+    }
+    for row in 1..=seq2.len() {
+        y[(row, 0)] = scoring.gap_open_score() + row as i32 * gap_score;
+    }
+
+    // fill in the matrices
+    for row in 1..=seq2.len() {
+        let current_seq2_char = seq2[row - 1];
+        for col in 1..=seq1.len() {
+            let best_diag = max(max(m[(row - 1, col - 1)], x[(row - 1, col - 1)]), y[(row - 1, col - 1)]);
+            m[(row, col)] = best_diag + scoring.substitution_score(seq1[col - 1], current_seq2_char);
+            x[(row, col)] = max(
+                max(m[(row, col - 1)] + open_score, x[(row, col - 1)] + gap_score),
+                y[(row, col - 1)] + open_score,
+            );
+            y[(row, col)] = max(
+                max(m[(row - 1, col)] + open_score, y[(row - 1, col)] + gap_score),
+                x[(row - 1, col)] + open_score,
+            );
+        }
+    }
+
+    AffineMatrices { m, x, y }
+}
+
+/// Find an optimal alignment in the Gotoh matrices.
+/// Gaps are extended rather than reopened when both are optimal.
+pub fn backtrack_affine_alignment<S: Scoring + ?Sized>(matrices: &AffineMatrices, seq1: &[u8], seq2: &[u8], scoring: &S) -> Alignment {
+    let AffineMatrices { m, x, y } = matrices;
+    let gap_score = scoring.gap_score();
+    let open_score = scoring.gap_open_score() + gap_score;
+
+    let mut current_row = seq2.len();
+    let mut current_col = seq1.len();
+    let score = matrices.score();
+    let mut state = if m[(current_row, current_col)] == score {
+        State::M
+    } else if x[(current_row, current_col)] == score {
+        State::X
+    } else {
+        State::Y
+    };
+
+    let mut alignment = AlignmentBuilder::with_capacity(seq1.len() + seq2.len());
+
+    while current_col != 0 || current_row != 0 {
+        match state {
+            State::M => {
+                let previous_score = m[(current_row, current_col)] - scoring.substitution_score(seq1[current_col - 1], seq2[current_row - 1]);
+                alignment.push_aligned(seq1[current_col - 1], seq2[current_row - 1]);
+                current_row -= 1;
+                current_col -= 1;
+                state = if m[(current_row, current_col)] == previous_score {
+                    State::M
+                } else if x[(current_row, current_col)] == previous_score {
+                    State::X
+                } else {
+                    State::Y
+                };
+            }
+            State::X => {
+                let current_score = x[(current_row, current_col)];
+                alignment.push_gap_in_seq2(seq1[current_col - 1]);
+                current_col -= 1;
+                state = if x[(current_row, current_col)] + gap_score == current_score {
+                    State::X
+                } else if m[(current_row, current_col)] + open_score == current_score {
+                    State::M
+                } else {
+                    State::Y
+                };
+            }
+            State::Y => {
+                let current_score = y[(current_row, current_col)];
+                alignment.push_gap_in_seq1(seq2[current_row - 1]);
+                current_row -= 1;
+                state = if y[(current_row, current_col)] + gap_score == current_score {
+                    State::Y
+                } else if m[(current_row, current_col)] + open_score == current_score {
+                    State::M
+                } else {
+                    State::X
+                };
+            }
+        }
+    }
+
+    alignment.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use fasta_reader::read_fasta;
+    use scoring::SimpleScoring;
+
+    use crate::{construct_matrix, score_alignment};
+    use crate::gotoh::{backtrack_affine_alignment, construct_affine_matrices};
+
+    #[test]
+    fn test_affine_without_open_score_equals_linear() -> Result<(), Box<dyn Error>> {
+        let (seq1, seq2) = read_fasta("../tests/verylarge.fasta")?;
+        let seq1_chars = seq1.into_bytes();
+        let seq2_chars = seq2.into_bytes();
+        let scoring = SimpleScoring::new(1, -1, -3);
+
+        let matrix = construct_matrix(&seq1_chars, &seq2_chars, &scoring);
+        let matrices = construct_affine_matrices(&seq1_chars, &seq2_chars, &scoring);
+        assert_eq!(matrices.score(), matrix[(seq2_chars.len(), seq1_chars.len())]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_affine_single_gap() {
+        // a single gap of length 2 (-10 - 2) beats 2 separate gaps or the mismatches of shifting the sequences
+        let seq1 = b"ACGTTTACGT".to_vec();
+        let seq2 = b"ACGTACGT".to_vec();
+        let scoring = SimpleScoring::new(2, -3, -1).with_gap_open_score(-10);
+
+        let matrices = construct_affine_matrices(&seq1, &seq2, &scoring);
+        assert_eq!(matrices.score(), 8 * 2 - 10 - 2);
+        let res = backtrack_affine_alignment(&matrices, &seq1, &seq2, &scoring);
+        assert_eq!(res.0, b"ACGTTTACGT".to_vec());
+        assert_eq!(res.2.iter().filter(|&&c| c == b'-').count(), 2);
+        assert_eq!(score_alignment(&res.0, &res.2, &scoring), matrices.score());
+    }
+
+    #[test]
+    fn test_affine_backtrack_score() -> Result<(), Box<dyn Error>> {
+        let (seq1, seq2) = read_fasta("../tests/verylarge.fasta")?;
+        let seq1_chars = seq1.into_bytes();
+        let seq2_chars = seq2.into_bytes();
+        let scoring = SimpleScoring::new(1, -1, -1).with_gap_open_score(-4);
+
+        let matrices = construct_affine_matrices(&seq1_chars, &seq2_chars, &scoring);
+        let (aligned_seq1, diff_line, aligned_seq2) = backtrack_affine_alignment(&matrices, &seq1_chars, &seq2_chars, &scoring);
+        assert_eq!(aligned_seq1.len(), diff_line.len());
+        assert_eq!(aligned_seq1.iter().filter(|&&c| c != b'-').copied().collect::<Vec<u8>>(), seq1_chars);
+        assert_eq!(aligned_seq2.iter().filter(|&&c| c != b'-').copied().collect::<Vec<u8>>(), seq2_chars);
+        assert_eq!(score_alignment(&aligned_seq1, &aligned_seq2, &scoring), matrices.score());
+        Ok(())
+    }
+
+    #[test]
+    fn test_affine_empty_sequence() {
+        let scoring = SimpleScoring::new(1, -1, -1).with_gap_open_score(-5);
+        let matrices = construct_affine_matrices(b"", b"test", &scoring);
+        assert_eq!(matrices.score(), -9);
+        let res = backtrack_affine_alignment(&matrices, b"", b"test", &scoring);
+        assert_eq!(res, (b"----".to_vec(), b"    ".to_vec(), b"test".to_vec()));
+
+        let matrices = construct_affine_matrices(b"", b"", &scoring);
+        assert_eq!(matrices.score(), 0);
+    }
+}
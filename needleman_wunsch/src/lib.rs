@@ -1,72 +1,213 @@
 use std::cmp::max;
 
-pub fn construct_matrix(seq1: &[u8], seq2: &[u8], match_score: i32, mismatch_score: i32, gap_score: i32) -> Vec<Vec<i32>> {
-    let mut matrix: Vec<Vec<i32>> = vec![vec![0; seq1.len() + 1]; seq2.len() + 1];
+use scoring::Scoring;
 
-    // initialize the first column and first row
-    matrix[0] = (0..=seq1.len()).map(|i| i as i32 * gap_score).collect();
-    for (index, init_value) in (0..seq2.len() + 1).enumerate() {
-        matrix[index][0] = init_value as i32 * gap_score;
+pub use crate::alignment_builder::AlignmentBuilder;
+pub use crate::cigar::{Cigar, CigarOp};
+pub use crate::cigar_errors::CigarError;
+pub use crate::co_optimal::{all_co_optimal_alignments, co_optimal_alignments, CoOptimalAlignments, TieBreak};
+pub use crate::dp_matrix::{DpMatrix, TRACE_DIAG, TRACE_LEFT, TRACE_UP};
+pub use crate::gotoh::{backtrack_affine_alignment, construct_affine_matrices, AffineMatrices};
+pub use crate::sam::{ReadAlignment, SamWriter};
+pub use crate::smith_waterman::{backtrack_local_alignment, construct_local_matrix, LocalAlignment};
+
+mod alignment_builder;
+mod cigar;
+mod cigar_errors;
+mod co_optimal;
+mod dp_matrix;
+mod gotoh;
+mod sam;
+mod smith_waterman;
+
+/// An alignment as 3 lines: the aligned seq1, a line marking matches (`|`) and mismatches (`*`), and the aligned seq2
+pub type Alignment = (Vec<u8>, Vec<u8>, Vec<u8>);
+
+/// Which end gaps are free, i.e. which leading or trailing characters of both sequences can be aligned to gaps without penalty.
+/// Without free end gaps (the default) the alignment is global.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EndGaps {
+    pub seq1_leading: bool,
+    pub seq1_trailing: bool,
+    pub seq2_leading: bool,
+    pub seq2_trailing: bool,
+}
+
+impl EndGaps {
+    /// Global alignment: no free end gaps
+    pub const GLOBAL: EndGaps = EndGaps { seq1_leading: false, seq1_trailing: false, seq2_leading: false, seq2_trailing: false };
+    /// Semi-global (or glocal) alignment: seq2 is aligned completely to any part of seq1, e.g. a read against a reference window
+    pub const SEMI_GLOBAL: EndGaps = EndGaps { seq1_leading: true, seq1_trailing: true, seq2_leading: false, seq2_trailing: false };
+    /// Overlap (dovetail) alignment: all end gaps are free, so a suffix of one sequence is aligned to a prefix of the other
+    /// (or one sequence is contained in the other)
+    pub const OVERLAP: EndGaps = EndGaps { seq1_leading: true, seq1_trailing: true, seq2_leading: true, seq2_trailing: true };
+}
+
+pub fn construct_matrix<S: Scoring + ?Sized>(seq1: &[u8], seq2: &[u8], scoring: &S) -> DpMatrix {
+    construct_matrix_with_end_gaps(seq1, seq2, scoring, EndGaps::GLOBAL)
+}
+
+/// Fill in the Needleman-Wunsch matrix, where the free leading gaps of `end_gaps` are not penalized
+pub fn construct_matrix_with_end_gaps<S: Scoring + ?Sized>(seq1: &[u8], seq2: &[u8], scoring: &S, end_gaps: EndGaps) -> DpMatrix {
+    fill_matrix(seq1, seq2, scoring, end_gaps, None)
+}
+
+/// Fill in the Needleman-Wunsch matrix like `construct_matrix_with_end_gaps`,
+/// together with a direction matrix that marks every move into a cell that gives its optimal score with the `TRACE_*` flags
+pub fn construct_matrix_with_directions<S: Scoring + ?Sized>(seq1: &[u8], seq2: &[u8], scoring: &S, end_gaps: EndGaps) -> (DpMatrix, DpMatrix<u8>) {
+    let mut directions = DpMatrix::new(seq2.len() + 1, seq1.len() + 1, 0);
+    let matrix = fill_matrix(seq1, seq2, scoring, end_gaps, Some(&mut directions));
+    (matrix, directions)
+}
+
+fn fill_matrix<S: Scoring + ?Sized>(seq1: &[u8], seq2: &[u8], scoring: &S, end_gaps: EndGaps, mut directions: Option<&mut DpMatrix<u8>>) -> DpMatrix {
+    let gap_score = scoring.gap_score();
+    let mut matrix = DpMatrix::new(seq2.len() + 1, seq1.len() + 1, 0);
+
+    // initialize the first column and first row, which stay 0 if the leading gaps are free
+    if !end_gaps.seq1_leading {
+        for (col, score) in matrix.row_mut(0).iter_mut().enumerate() {
+            *score = col as i32 * gap_score;
+        }
+    }
+    if !end_gaps.seq2_leading {
+        for row in 0..=seq2.len() {
+            matrix[(row, 0)] = row as i32 * gap_score;
+        }
+    }
+    if let Some(directions) = directions.as_deref_mut() {
+        // the first row and column can only be reached with gaps
+        directions.row_mut(0).fill(TRACE_LEFT);
+        for row in 0..=seq2.len() {
+            directions[(row, 0)] = TRACE_UP;
+        }
+        directions[(0, 0)] = 0;
     }
 
     // fill in the matrix
     for row in 1..=seq2.len() {
         let current_seq2_char = seq2[row - 1];
         for col in 1..=seq1.len() {
-            let diag_score = matrix[row - 1][col - 1] + if seq1[col - 1] == current_seq2_char { match_score } else { mismatch_score };
-            matrix[row][col] = max(
-                max(diag_score, matrix[row][col - 1] + gap_score),
-                matrix[row - 1][col] + gap_score,
-            );
+            let diag_score = matrix[(row - 1, col - 1)] + scoring.substitution_score(seq1[col - 1], current_seq2_char);
+            let left_score = matrix[(row, col - 1)] + gap_score;
+            let up_score = matrix[(row - 1, col)] + gap_score;
+            let score = max(max(diag_score, left_score), up_score);
+            matrix[(row, col)] = score;
+
+            if let Some(directions) = directions.as_deref_mut() {
+                let direction = &mut directions[(row, col)];
+                if diag_score == score {
+                    *direction |= TRACE_DIAG;
+                }
+                if left_score == score {
+                    *direction |= TRACE_LEFT;
+                }
+                if up_score == score {
+                    *direction |= TRACE_UP;
+                }
+            }
         }
     }
 
     matrix
 }
 
-pub fn backtrack_alignment(matrix: &Vec<Vec<i32>>, seq1: &[u8], seq2: &[u8], gap_score: i32) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
-    let mut current_row = seq2.len();
-    let mut current_col = seq1.len();
+/// The cell where the optimal alignment ends, as (row, col): the bottom right cell,
+/// or the best cell of the last row or column if the trailing gaps of seq1 or seq2 are free
+pub fn best_end_cell(matrix: &DpMatrix, end_gaps: EndGaps) -> (usize, usize) {
+    best_end_cells(matrix, end_gaps)[0]
+}
+
+/// All cells where an optimal alignment can end, when several cells of the last row or column have the optimal score.
+/// The bottom right cell comes first, then the cells of the last row and then those of the last column, each with the fewest trailing gaps first.
+pub fn best_end_cells(matrix: &DpMatrix, end_gaps: EndGaps) -> Vec<(usize, usize)> {
+    let last_row = matrix.rows() - 1;
+    let last_col = matrix.cols() - 1;
+
+    let mut cells = vec![(last_row, last_col)];
+    if end_gaps.seq1_trailing {
+        cells.extend((0..last_col).rev().map(|col| (last_row, col)));
+    }
+    if end_gaps.seq2_trailing {
+        cells.extend((0..last_row).rev().map(|row| (row, last_col)));
+    }
+    let best_score = cells.iter().map(|&cell| matrix[cell]).max().expect("The bottom right cell is always a candidate");
+    cells.retain(|&cell| matrix[cell] == best_score);
+    cells
+}
+
+pub fn backtrack_alignment<S: Scoring + ?Sized>(matrix: &DpMatrix, seq1: &[u8], seq2: &[u8], scoring: &S) -> Alignment {
+    backtrack_alignment_with_end_gaps(matrix, seq1, seq2, scoring, EndGaps::GLOBAL)
+}
+
+/// Find an optimal alignment in a matrix built by `construct_matrix_with_end_gaps` with the same `end_gaps`.
+/// The traceback starts in `best_end_cell`, the characters after that cell are aligned to trailing gaps.
+pub fn backtrack_alignment_with_end_gaps<S: Scoring + ?Sized>(matrix: &DpMatrix, seq1: &[u8], seq2: &[u8], scoring: &S, end_gaps: EndGaps) -> Alignment {
+    let gap_score = scoring.gap_score();
+    let (mut current_row, mut current_col) = best_end_cell(matrix, end_gaps);
+
+    let mut alignment = AlignmentBuilder::with_capacity(seq1.len() + seq2.len());
 
-    let mut aligned_seq1: Vec<u8> = vec![];
-    let mut aligned_seq2: Vec<u8> = vec![];
-    let mut diff_line: Vec<u8> = vec![];
+    // the trailing gaps, at most one of both loops adds characters
+    for &char1 in seq1[current_col..].iter().rev() {
+        alignment.push_gap_in_seq2(char1);
+    }
+    for &char2 in seq2[current_row..].iter().rev() {
+        alignment.push_gap_in_seq1(char2);
+    }
 
     while current_col != 0 || current_row != 0 {
-        if current_col != 0 && matrix[current_row][current_col] == matrix[current_row][current_col - 1] + gap_score {
-            aligned_seq1.insert(0, seq1[current_col - 1]);
-            diff_line.insert(0, b' ');
-            aligned_seq2.insert(0, b'-');
+        // the first row and column can only be reached with gaps, which are not reflected in the scores when they are free
+        if current_col != 0 && (current_row == 0 || matrix[(current_row, current_col)] == matrix[(current_row, current_col - 1)] + gap_score) {
+            alignment.push_gap_in_seq2(seq1[current_col - 1]);
             current_col -= 1;
             continue;
         }
 
-        if current_row != 0 && matrix[current_row][current_col] == matrix[current_row - 1][current_col] + gap_score {
-            aligned_seq1.insert(0, b'-');
-            diff_line.insert(0, b' ');
-            aligned_seq2.insert(0, seq2[current_row - 1]);
+        if current_row != 0 && (current_col == 0 || matrix[(current_row, current_col)] == matrix[(current_row - 1, current_col)] + gap_score) {
+            alignment.push_gap_in_seq1(seq2[current_row - 1]);
             current_row -= 1;
             continue;
         }
 
         // diagonal case
-        aligned_seq1.insert(0, seq1[current_col - 1]);
-        aligned_seq2.insert(0, seq2[current_row - 1]);
-        let char_to_insert = if seq1[current_col - 1] == seq2[current_row - 1] { b'|' } else { b'*' };
-        diff_line.insert(0, char_to_insert);
+        alignment.push_aligned(seq1[current_col - 1], seq2[current_row - 1]);
         current_row -= 1;
         current_col -= 1;
     }
 
-    (aligned_seq1, diff_line, aligned_seq2)
+    alignment.finish()
 }
 
+/// Find an optimal alignment in a direction matrix built by `construct_matrix_with_directions`, starting in `end_cell`
+/// (see `best_end_cell`). The scores are not needed anymore, ties are broken according to `tie_break`.
+pub fn backtrack_alignment_from_directions(directions: &DpMatrix<u8>, seq1: &[u8], seq2: &[u8], end_cell: (usize, usize), tie_break: TieBreak) -> Alignment {
+    co_optimal_alignments(directions, seq1, seq2, end_cell, tie_break).next().expect("Every cell is reached from the first cell")
+}
+
+/// Calculate the score of an alignment, where consecutive gaps in the same sequence are scored as a single affine gap
+pub fn score_alignment<S: Scoring + ?Sized>(aligned_seq1: &[u8], aligned_seq2: &[u8], scoring: &S) -> i32 {
+    let mut score = 0;
+    // the sequence in which the previous column had a gap, if any
+    let mut previous_gap = None;
+    for (&char1, &char2) in aligned_seq1.iter().zip(aligned_seq2) {
+        let gap = if char1 == b'-' { Some(1) } else if char2 == b'-' { Some(2) } else { None };
+        score += match gap {
+            Some(_) if gap == previous_gap => scoring.gap_score(),
+            Some(_) => scoring.gap_open_score() + scoring.gap_score(),
+            None => scoring.substitution_score(char1, char2),
+        };
+        previous_gap = gap;
+    }
+    score
+}
 
 #[cfg(test)]
 mod tests {
     use std::error::Error;
     use fasta_reader::read_fasta;
-    use crate::{backtrack_alignment, construct_matrix};
+    use scoring::SimpleScoring;
+    use crate::{backtrack_alignment, backtrack_alignment_from_directions, backtrack_alignment_with_end_gaps, best_end_cell, construct_matrix, construct_matrix_with_directions, construct_matrix_with_end_gaps, EndGaps, TieBreak, TRACE_DIAG, TRACE_LEFT, TRACE_UP};
 
     #[test]
     fn test_matrix() -> Result<(), Box<dyn Error>> {
@@ -74,8 +215,33 @@ mod tests {
         let seq1_chars = seq1.into_bytes();
         let seq2_chars = seq2.into_bytes();
 
-        let res = construct_matrix(&seq1_chars, &seq2_chars, 1, -1, -1);
-        assert_eq!(res[res.len() - 1], vec![-7, -5, -3, -1, -1, -1, 0, 0]);
+        let res = construct_matrix(&seq1_chars, &seq2_chars, &SimpleScoring::new(1, -1, -1));
+        assert_eq!(res.row(res.rows() - 1), &[-7, -5, -3, -1, -1, -1, 0, 0]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_backtrack_alignment_from_directions() -> Result<(), Box<dyn Error>> {
+        let (seq1, seq2) = read_fasta("../tests/dna_input.fasta")?;
+        let seq1_chars = seq1.into_bytes();
+        let seq2_chars = seq2.into_bytes();
+        let scoring = SimpleScoring::new(1, -1, -1);
+
+        for end_gaps in [EndGaps::GLOBAL, EndGaps::SEMI_GLOBAL, EndGaps::OVERLAP] {
+            let (matrix, directions) = construct_matrix_with_directions(&seq1_chars, &seq2_chars, &scoring, end_gaps);
+            assert_eq!(matrix, construct_matrix_with_end_gaps(&seq1_chars, &seq2_chars, &scoring, end_gaps));
+            let end_cell = best_end_cell(&matrix, end_gaps);
+            assert_eq!(
+                backtrack_alignment_from_directions(&directions, &seq1_chars, &seq2_chars, end_cell, TieBreak::default()),
+                backtrack_alignment_with_end_gaps(&matrix, &seq1_chars, &seq2_chars, &scoring, end_gaps)
+            );
+        }
+
+        // both the diagonal and a gap are optimal in the bottom right corner
+        let (_, directions) = construct_matrix_with_directions(b"AA", b"A", &scoring, EndGaps::GLOBAL);
+        assert_eq!(directions[(1, 2)], TRACE_DIAG | TRACE_LEFT);
+        assert_eq!(directions[(0, 2)], TRACE_LEFT);
+        assert_eq!(directions[(1, 0)], TRACE_UP);
         Ok(())
     }
 
@@ -86,8 +252,8 @@ mod tests {
         let seq1_chars = seq1.into_bytes();
         let seq2_chars = seq2.into_bytes();
 
-        let matrix = construct_matrix(&seq1_chars, &seq2_chars, 1, -1, -1);
-        let res = backtrack_alignment(&matrix, &seq1_chars, &seq2_chars, -1);
+        let matrix = construct_matrix(&seq1_chars, &seq2_chars, &SimpleScoring::new(1, -1, -1));
+        let res = backtrack_alignment(&matrix, &seq1_chars, &seq2_chars, &SimpleScoring::new(1, -1, -1));
         assert_eq!(res, ("G-ATTACA".to_string().into_bytes(), "| ||* |*".to_string().into_bytes(), "GCATG-CU".to_string().into_bytes()));
         Ok(())
     }
@@ -99,8 +265,8 @@ mod tests {
         let seq1_chars = seq1.into_bytes();
         let seq2_chars = seq2.into_bytes();
 
-        let matrix = construct_matrix(&seq1_chars, &seq2_chars, 1, -1, -1);
-        let res = backtrack_alignment(&matrix, &seq1_chars, &seq2_chars, -1);
+        let matrix = construct_matrix(&seq1_chars, &seq2_chars, &SimpleScoring::new(1, -1, -1));
+        let res = backtrack_alignment(&matrix, &seq1_chars, &seq2_chars, &SimpleScoring::new(1, -1, -1));
         assert_eq!(res, ("test".to_string().into_bytes(), "    ".to_string().into_bytes(), "----".to_string().into_bytes()));
         Ok(())
     }
@@ -112,9 +278,60 @@ mod tests {
         let seq1_chars = seq1.into_bytes();
         let seq2_chars = seq2.into_bytes();
 
-        let matrix = construct_matrix(&seq1_chars, &seq2_chars, 1, -1, -1);
-        let res = backtrack_alignment(&matrix, &seq1_chars, &seq2_chars, -1);
+        let matrix = construct_matrix(&seq1_chars, &seq2_chars, &SimpleScoring::new(1, -1, -1));
+        let res = backtrack_alignment(&matrix, &seq1_chars, &seq2_chars, &SimpleScoring::new(1, -1, -1));
         assert_eq!(res, ("----".to_string().into_bytes(), "    ".to_string().into_bytes(), "test".to_string().into_bytes()));
         Ok(())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_semi_global() {
+        let seq1 = b"TTTTGATTACATTTT".to_vec();
+        let seq2 = b"GATTACA".to_vec();
+        let scoring = SimpleScoring::new(1, -1, -1);
+
+        let matrix = construct_matrix_with_end_gaps(&seq1, &seq2, &scoring, EndGaps::SEMI_GLOBAL);
+        let (row, col) = best_end_cell(&matrix, EndGaps::SEMI_GLOBAL);
+        assert_eq!((row, col), (7, 11));
+        assert_eq!(matrix[(row, col)], 7);
+        let res = backtrack_alignment_with_end_gaps(&matrix, &seq1, &seq2, &scoring, EndGaps::SEMI_GLOBAL);
+        assert_eq!(res, (b"TTTTGATTACATTTT".to_vec(), b"    |||||||    ".to_vec(), b"----GATTACA----".to_vec()));
+    }
+
+    #[test]
+    fn test_overlap() {
+        // a suffix of seq1 overlaps with a prefix of seq2
+        let seq1 = b"ACGTACGTGGCC".to_vec();
+        let seq2 = b"GGCCTTAA".to_vec();
+        let scoring = SimpleScoring::new(1, -1, -1);
+
+        let matrix = construct_matrix_with_end_gaps(&seq1, &seq2, &scoring, EndGaps::OVERLAP);
+        let (row, col) = best_end_cell(&matrix, EndGaps::OVERLAP);
+        assert_eq!(matrix[(row, col)], 4);
+        let res = backtrack_alignment_with_end_gaps(&matrix, &seq1, &seq2, &scoring, EndGaps::OVERLAP);
+        assert_eq!(res, (b"ACGTACGTGGCC----".to_vec(), b"        ||||    ".to_vec(), b"--------GGCCTTAA".to_vec()));
+
+        // the same overlap is found when only the ends of the overlap are free
+        let end_gaps = EndGaps { seq1_leading: true, seq2_trailing: true, ..EndGaps::GLOBAL };
+        let matrix = construct_matrix_with_end_gaps(&seq1, &seq2, &scoring, end_gaps);
+        assert_eq!(backtrack_alignment_with_end_gaps(&matrix, &seq1, &seq2, &scoring, end_gaps), res);
+    }
+
+    #[test]
+    fn test_global_end_gaps() -> Result<(), Box<dyn Error>> {
+        let (seq1, seq2) = read_fasta("../tests/verylarge.fasta")?;
+        let seq1_chars = seq1.into_bytes();
+        let seq2_chars = seq2.into_bytes();
+        let scoring = SimpleScoring::new(1, -1, -3);
+
+        let matrix = construct_matrix(&seq1_chars, &seq2_chars, &scoring);
+        assert_eq!(construct_matrix_with_end_gaps(&seq1_chars, &seq2_chars, &scoring, EndGaps::default()), matrix);
+        assert_eq!(best_end_cell(&matrix, EndGaps::GLOBAL), (seq2_chars.len(), seq1_chars.len()));
+
+        // free end gaps can only improve the score
+        let matrix_overlap = construct_matrix_with_end_gaps(&seq1_chars, &seq2_chars, &scoring, EndGaps::OVERLAP);
+        let (row, col) = best_end_cell(&matrix_overlap, EndGaps::OVERLAP);
+        assert!(matrix_overlap[(row, col)] >= matrix[(seq2_chars.len(), seq1_chars.len())]);
+        Ok(())
+    }
+}
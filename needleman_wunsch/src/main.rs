@@ -1,15 +1,81 @@
 use std::error::Error;
+use std::io;
 use std::str::from_utf8;
-use clap::Parser;
-use fasta_reader::read_fasta;
-use needleman_wunsch::{backtrack_alignment, construct_matrix};
+use clap::{Parser, ValueEnum};
+use fasta_reader::{read_fasta, read_fasta_regions, read_records};
+use needleman_wunsch::{backtrack_affine_alignment, backtrack_local_alignment, all_co_optimal_alignments, best_end_cells, construct_affine_matrices, construct_local_matrix, construct_matrix_with_directions, Alignment, Cigar, EndGaps, ReadAlignment, SamWriter, TieBreak};
+use scoring::{Scoring, SimpleScoring, SubstitutionMatrix};
+
+/// The kind of alignment to compute
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Mode {
+    /// Needleman-Wunsch: align both sequences completely
+    Global,
+    /// Smith-Waterman: align the most similar parts of both sequences
+    Local,
+    /// Align sequence 2 completely to any part of sequence 1: the end gaps in sequence 1 are free
+    #[value(alias = "glocal")]
+    SemiGlobal,
+    /// Align a suffix of one sequence to a prefix of the other: all end gaps are free
+    Overlap,
+}
+
+impl Mode {
+    /// The free end gaps of the modes that use the Needleman-Wunsch matrix
+    fn end_gaps(self) -> Option<EndGaps> {
+        match self {
+            Mode::Global => Some(EndGaps::GLOBAL),
+            Mode::Local => None,
+            Mode::SemiGlobal => Some(EndGaps::SEMI_GLOBAL),
+            Mode::Overlap => Some(EndGaps::OVERLAP),
+        }
+    }
+}
+
+/// Which optimal move the traceback takes when there is more than one
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum TieBreakArg {
+    /// Shift gaps as far right as possible
+    Right,
+    /// Shift gaps as far left as possible, as in the left-normalisation of indels
+    Left,
+    /// Prefer matches over gaps, and gaps over mismatches
+    Match,
+}
+
+impl TieBreakArg {
+    fn tie_break(self) -> TieBreak {
+        match self {
+            TieBreakArg::Right => TieBreak::GapsRightAligned,
+            TieBreakArg::Left => TieBreak::GapsLeftAligned,
+            TieBreakArg::Match => TieBreak::PreferMatch,
+        }
+    }
+}
+
+/// How the alignment is printed
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    /// The aligned sequences, with a line marking matches (`|`) and mismatches (`*`) in between
+    Alignment,
+    /// An extended CIGAR string, with `=` for matches and `X` for mismatches (sequence 1 is the reference)
+    Cigar,
+    /// A classic CIGAR string, with `M` for both matches and mismatches (sequence 1 is the reference)
+    ClassicCigar,
+    /// A SAM file with the second record as read aligned to the first record as reference
+    Sam,
+}
 
 #[derive(Parser, Debug)]
 #[clap(allow_negative_numbers = true)]
 struct Args {
-    /// The input file name
+    /// The input file name (FASTA or FASTQ)
     #[clap(short, long)]
     filename: String,
+    /// Fetch the 2 sequences to align from the indexed input file instead of using the first 2 records.
+    /// Each region is written as `name`, `name:start` or `name:start-end` (1-based, inclusive), e.g. `--region chr1:1000-2000 read1`
+    #[clap(short, long, num_args = 2, value_names = ["REGION1", "REGION2"])]
+    region: Option<Vec<String>>,
     /// The score used when there is a match
     #[clap(short, long, default_value_t = 1)]
     match_score: i32,
@@ -19,24 +85,103 @@ struct Args {
     /// The score used when there is a gap
     #[clap(short, long, default_value_t = - 3)]
     gap_score: i32,
+    /// The substitution matrix used instead of the match and mismatch score:
+    /// a built-in matrix (BLOSUM62, PAM40, PAM120 or PAM250) or the path to a matrix file in the NCBI format
+    #[clap(long)]
+    matrix: Option<String>,
+    /// Use affine gaps (Gotoh): the extra score for opening a gap, on top of the gap score for every character in the gap
+    #[clap(short = 'o', long)]
+    gap_open_score: Option<i32>,
+    /// The kind of alignment to compute
+    #[clap(long, value_enum, default_value_t = Mode::Global)]
+    mode: Mode,
+    /// Which optimal move to take when more than one move gives the optimal score (right-aligned gaps by default)
+    #[clap(long, value_enum)]
+    tie_break: Option<TieBreakArg>,
+    /// Print up to this many co-optimal alignments instead of a single one
+    #[clap(long)]
+    max_alignments: Option<usize>,
+    /// How the alignment is printed
+    #[clap(long, value_enum, default_value_t = Format::Alignment)]
+    format: Format,
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
-    let Args { filename, match_score, mismatch_score, gap_score } = args;
-    let (seq1, seq2) = read_fasta(&filename)?;
+    let Args { filename, region, match_score, mismatch_score, gap_score, matrix, gap_open_score, mode, tie_break, max_alignments, format } = args;
+    if format == Format::Sam && (region.is_some() || mode == Mode::Local || max_alignments.is_some_and(|max_alignments| max_alignments > 1)) {
+        return Err("SAM output is only supported for a single alignment of the first 2 records, without --region and the local mode".into());
+    }
+    let (seq1, seq2) = match region {
+        Some(regions) => read_fasta_regions(&filename, &regions[0], &regions[1])?,
+        None => read_fasta(&filename)?,
+    };
     let seq1_chars = seq1.into_bytes();
     let seq2_chars = seq2.into_bytes();
 
-    let matrix = construct_matrix(&seq1_chars, &seq2_chars, match_score, mismatch_score, gap_score);
-    println!("The score for optimal alignment is: {}", matrix[seq2_chars.len()][seq1_chars.len()]);
-    let (aligned_seq1, diff_line, aligned_seq2) = backtrack_alignment(&matrix, &seq1_chars, &seq2_chars, gap_score);
+    let scoring: Box<dyn Scoring> = match matrix {
+        Some(matrix) => Box::new(SubstitutionMatrix::from_name_or_file(&matrix, gap_score)?.with_gap_open_score(gap_open_score.unwrap_or(0))),
+        None => Box::new(SimpleScoring::new(match_score, mismatch_score, gap_score).with_gap_open_score(gap_open_score.unwrap_or(0))),
+    };
+    if gap_open_score.is_some() && mode != Mode::Global {
+        return Err("Affine gaps are only supported for global alignment".into());
+    }
+    if (tie_break.is_some() || max_alignments.is_some()) && (mode.end_gaps().is_none() || gap_open_score.is_some()) {
+        return Err("The tie break and co-optimal alignments are only supported for the global, semi-global and overlap mode with linear gaps".into());
+    }
+    let (score, alignments): (i32, Vec<Alignment>) = match mode.end_gaps() {
+        None => {
+            let matrix = construct_local_matrix(&seq1_chars, &seq2_chars, scoring.as_ref());
+            let local_alignment = backtrack_local_alignment(&matrix, &seq1_chars, &seq2_chars, scoring.as_ref());
+            println!("The score for optimal local alignment is: {}", local_alignment.score);
+            // print the aligned parts as 1-based, inclusive coordinates
+            println!("Aligned part of sequence 1: {}-{}", local_alignment.seq1_start + 1, local_alignment.seq1_end);
+            println!("Aligned part of sequence 2: {}-{}", local_alignment.seq2_start + 1, local_alignment.seq2_end);
+            (local_alignment.score, vec![local_alignment.alignment])
+        }
+        Some(_) if gap_open_score.is_some() => {
+            let matrices = construct_affine_matrices(&seq1_chars, &seq2_chars, scoring.as_ref());
+            (matrices.score(), vec![backtrack_affine_alignment(&matrices, &seq1_chars, &seq2_chars, scoring.as_ref())])
+        }
+        Some(end_gaps) => {
+            let (matrix, directions) = construct_matrix_with_directions(&seq1_chars, &seq2_chars, scoring.as_ref(), end_gaps);
+            // with free trailing gaps, several cells can have the optimal score
+            let end_cells = best_end_cells(&matrix, end_gaps);
+            let score = matrix[end_cells[0]];
+            let tie_break = tie_break.map_or(TieBreak::default(), TieBreakArg::tie_break);
+            (score, all_co_optimal_alignments(&directions, &seq1_chars, &seq2_chars, end_cells, tie_break).take(max_alignments.unwrap_or(1)).collect())
+        }
+    };
+
+    if format == Format::Sam {
+        // the records are read again for their names and quality strings
+        let records = read_records(&filename, 2)?;
+        let mut writer = SamWriter::new(io::stdout().lock(), &[&records[0]], "needleman_wunsch")?;
+        writer.write_record(&records[1], &records[0], &ReadAlignment::from_alignment(&alignments[0], score))?;
+        return Ok(());
+    }
+    if mode != Mode::Local {
+        println!("The score for optimal alignment is: {}", score);
+    }
 
-    println!();
-    println!("Aligned sequences:");
-    println!("{}", from_utf8(&*aligned_seq1)?);
-    println!("{}", from_utf8(&*diff_line)?);
-    println!("{}", from_utf8(&*aligned_seq2)?);
+    for (aligned_seq1, diff_line, aligned_seq2) in alignments {
+        match format {
+            Format::Alignment => {
+                println!();
+                println!("Aligned sequences:");
+                println!("{}", from_utf8(&aligned_seq1)?);
+                println!("{}", from_utf8(&diff_line)?);
+                println!("{}", from_utf8(&aligned_seq2)?);
+            }
+            Format::Cigar | Format::ClassicCigar => {
+                let cigar = Cigar::from_alignment(&(aligned_seq1, diff_line, aligned_seq2));
+                println!();
+                println!("CIGAR:");
+                println!("{}", if format == Format::Cigar { cigar } else { cigar.to_classic() });
+            }
+            Format::Sam => unreachable!("The SAM output is written above"),
+        }
+    }
 
     Ok(())
 }
@@ -0,0 +1,170 @@
+use std::io;
+use std::io::Write;
+
+use fasta_reader::Record;
+
+use crate::{Alignment, Cigar, CigarOp};
+
+/// The SAM flag of a read that is not aligned to the reference
+const FLAG_UNMAPPED: u16 = 4;
+/// The SAM mapping quality that marks it as unavailable
+const MAPQ_UNAVAILABLE: u8 = 255;
+
+/// The alignment of a read (seq2) to a reference (seq1), with the fields of a SAM record that depend on the alignment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadAlignment {
+    /// 0-based position in the reference of the first reference character aligned to the read
+    pub position: usize,
+    /// The CIGAR from `position` on, extended by `from_alignment` (use `Cigar::to_classic` for tools that only know `M`)
+    pub cigar: Cigar,
+    /// The score of the alignment, written as the `AS:i` tag
+    pub score: i32,
+    /// The number of mismatches and inserted and deleted characters, written as the `NM:i` tag
+    pub edit_distance: usize,
+    /// The reference characters at the mismatches and deletions, written as the `MD:Z` tag
+    pub mismatches: String,
+}
+
+impl ReadAlignment {
+    /// The read alignment of seq2 in an alignment of seq1 and seq2 with the given score.
+    /// The reference characters that are aligned to gaps before and after the read are left out, they only move the position.
+    pub fn from_alignment(alignment: &Alignment, score: i32) -> ReadAlignment {
+        let (aligned_seq1, _, aligned_seq2) = alignment;
+        let start = aligned_seq2.iter().position(|&char2| char2 != b'-').unwrap_or(aligned_seq2.len());
+        let end = aligned_seq2.iter().rposition(|&char2| char2 != b'-').map_or(start, |end| end + 1);
+        let (aligned_seq1, aligned_seq2) = (&aligned_seq1[start..end], &aligned_seq2[start..end]);
+
+        let mut edit_distance = 0;
+        // the MD tag: the number of matches between every mismatch and deletion, which are written as the reference characters
+        let mut mismatches = String::new();
+        let mut matches = 0;
+        let mut in_deletion = false;
+        for (&char1, &char2) in aligned_seq1.iter().zip(aligned_seq2) {
+            match (char1, char2) {
+                (b'-', _) => {
+                    // insertions are not in the MD tag, but 2 deletions around them are separate
+                    edit_distance += 1;
+                    in_deletion = false;
+                }
+                (_, b'-') => {
+                    if !in_deletion {
+                        mismatches.push_str(&format!("{}^", matches));
+                        matches = 0;
+                        in_deletion = true;
+                    }
+                    mismatches.push(char::from(char1));
+                    edit_distance += 1;
+                }
+                _ if char1 == char2 => {
+                    matches += 1;
+                    in_deletion = false;
+                }
+                _ => {
+                    mismatches.push_str(&format!("{}{}", matches, char::from(char1)));
+                    matches = 0;
+                    edit_distance += 1;
+                    in_deletion = false;
+                }
+            }
+        }
+        mismatches.push_str(&matches.to_string());
+
+        let cigar = Cigar::from_alignment(&(aligned_seq1.to_vec(), vec![], aligned_seq2.to_vec()));
+        // every column before the read has a reference character
+        ReadAlignment { position: start, cigar, score, edit_distance, mismatches }
+    }
+
+    /// Whether at least 1 character of the read is aligned to the reference
+    fn is_mapped(&self) -> bool {
+        self.cigar.operations().iter().any(|&(_, operation)| operation != CigarOp::Insertion)
+    }
+}
+
+/// Writer of SAM files with unpaired reads, which can be viewed with IGV or converted to BAM with `samtools view`
+pub struct SamWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> SamWriter<W> {
+    /// Start a SAM file by writing its header: a `@SQ` line for every reference and a `@PG` line for the program
+    pub fn new(mut writer: W, references: &[&Record], program: &str) -> io::Result<SamWriter<W>> {
+        writeln!(writer, "@HD\tVN:1.6\tSO:unsorted")?;
+        for reference in references {
+            writeln!(writer, "@SQ\tSN:{}\tLN:{}", reference.id, reference.seq.len())?;
+        }
+        writeln!(writer, "@PG\tID:{0}\tPN:{0}", program)?;
+        Ok(SamWriter { writer })
+    }
+
+    /// Write the alignment of `read` to `reference` as a SAM record. A read without any aligned characters is written as unmapped.
+    /// The quality string of a FASTQ read is written as well.
+    pub fn write_record(&mut self, read: &Record, reference: &Record, alignment: &ReadAlignment) -> io::Result<()> {
+        let seq = if read.seq.is_empty() { "*".into() } else { String::from_utf8_lossy(&read.seq) };
+        let qual = read.qual.as_ref().map_or("*".into(), |qual| String::from_utf8_lossy(qual));
+        if !alignment.is_mapped() {
+            return writeln!(self.writer, "{}\t{}\t*\t0\t0\t*\t*\t0\t0\t{}\t{}", read.id, FLAG_UNMAPPED, seq, qual);
+        }
+
+        // the position is 1-based in SAM
+        writeln!(
+            self.writer,
+            "{}\t0\t{}\t{}\t{}\t{}\t*\t0\t0\t{}\t{}\tAS:i:{}\tNM:i:{}\tMD:Z:{}",
+            read.id, reference.id, alignment.position + 1, MAPQ_UNAVAILABLE, alignment.cigar, seq, qual, alignment.score, alignment.edit_distance, alignment.mismatches
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use fasta_reader::Record;
+
+    use crate::{Cigar, ReadAlignment, SamWriter};
+
+    fn record(id: &str, seq: &[u8], qual: Option<&[u8]>) -> Record {
+        Record { id: id.to_string(), description: None, seq: seq.to_vec(), qual: qual.map(<[u8]>::to_vec) }
+    }
+
+    #[test]
+    fn test_read_alignment() {
+        // the reference characters before and after the read move the position and are left out of the CIGAR
+        let alignment = (b"GGACGTTA-CC".to_vec(), b"  ||*|||   ".to_vec(), b"--ACTTTAG--".to_vec());
+        let read_alignment = ReadAlignment::from_alignment(&alignment, 3);
+        assert_eq!(read_alignment.position, 2);
+        assert_eq!(read_alignment.cigar.to_string(), "2=1X3=1I");
+        assert_eq!(read_alignment.edit_distance, 2);
+        assert_eq!(read_alignment.mismatches, "2G3");
+
+        let read_alignment = ReadAlignment::from_alignment(&(b"ACGTTA".to_vec(), b"||   |".to_vec(), b"AC---A".to_vec()), 0);
+        assert_eq!((read_alignment.cigar.to_string(), read_alignment.edit_distance, read_alignment.mismatches), ("2=3D1=".to_string(), 3, "2^GTT1".to_string()));
+
+        // a mismatch right after a deletion and 2 deletions around an insertion are separated by 0 matches
+        let read_alignment = ReadAlignment::from_alignment(&(b"ACGTAG".to_vec(), b"||  *|".to_vec(), b"AC--TG".to_vec()), 0);
+        assert_eq!(read_alignment.mismatches, "2^GT0A1");
+        let read_alignment = ReadAlignment::from_alignment(&(b"AC-GTA".to_vec(), b"|    |".to_vec(), b"A-T-TA".to_vec()), 0);
+        assert_eq!(read_alignment.mismatches, "1^C0^G2");
+        assert_eq!(read_alignment.edit_distance, 3);
+    }
+
+    #[test]
+    fn test_sam_writer() -> Result<(), Box<dyn Error>> {
+        let reference = record("chr1", b"GGACGTTACC", None);
+        let read = record("read1", b"ACTTTAG", Some(b"IIIII#I"));
+        let alignment = ReadAlignment::from_alignment(&(b"GGACGTTA-CC".to_vec(), b"  ||*|||   ".to_vec(), b"--ACTTTAG--".to_vec()), 3);
+
+        let mut output = vec![];
+        let mut writer = SamWriter::new(&mut output, &[&reference], "needleman_wunsch")?;
+        writer.write_record(&read, &reference, &alignment)?;
+        let unmapped = ReadAlignment { cigar: "2I".parse::<Cigar>()?, ..alignment };
+        writer.write_record(&record("read2", b"AC", None), &reference, &unmapped)?;
+
+        let expected = "@HD\tVN:1.6\tSO:unsorted\n\
+            @SQ\tSN:chr1\tLN:10\n\
+            @PG\tID:needleman_wunsch\tPN:needleman_wunsch\n\
+            read1\t0\tchr1\t3\t255\t2=1X3=1I\t*\t0\t0\tACTTTAG\tIIIII#I\tAS:i:3\tNM:i:2\tMD:Z:2G3\n\
+            read2\t4\t*\t0\t0\t*\t*\t0\t0\tAC\t*\n";
+        assert_eq!(String::from_utf8(output)?, expected);
+        Ok(())
+    }
+}
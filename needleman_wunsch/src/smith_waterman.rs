@@ -0,0 +1,140 @@
+use std::cmp::max;
+
+use scoring::Scoring;
+
+use crate::{Alignment, AlignmentBuilder, DpMatrix};
+
+/// The best local alignment of 2 sequences, together with the part of both sequences it covers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalAlignment {
+    pub score: i32,
+    /// The aligned part of seq1 is `seq1[seq1_start..seq1_end]` (0-based, end exclusive)
+    pub seq1_start: usize,
+    pub seq1_end: usize,
+    /// The aligned part of seq2 is `seq2[seq2_start..seq2_end]` (0-based, end exclusive)
+    pub seq2_start: usize,
+    pub seq2_end: usize,
+    pub alignment: Alignment,
+}
+
+/// Fill in the Smith-Waterman matrix for local alignment, with seq2 along the rows and seq1 along the columns.
+/// Every cell is at least 0, since a local alignment can start anywhere.
+pub fn construct_local_matrix<S: Scoring + ?Sized>(seq1: &[u8], seq2: &[u8], scoring: &S) -> DpMatrix {
+    let gap_score = scoring.gap_score();
+    // the first row and column stay 0
+    let mut matrix = DpMatrix::new(seq2.len() + 1, seq1.len() + 1, 0);
+
+    for row in 1..=seq2.len() {
+        let current_seq2_char = seq2[row - 1];
+        for col in 1..=seq1.len() {
+            let diag_score = matrix[(row - 1, col - 1)] + scoring.substitution_score(seq1[col - 1], current_seq2_char);
+            matrix[(row, col)] = max(
+                max(max(diag_score, matrix[(row, col - 1)] + gap_score), matrix[(row - 1, col)] + gap_score),
+                0,
+            );
+        }
+    }
+
+    matrix
+}
+
+/// Find the best local alignment in the Smith-Waterman matrix: start in the cell with the highest score,
+/// and trace back until a cell with score 0 is reached
+pub fn backtrack_local_alignment<S: Scoring + ?Sized>(matrix: &DpMatrix, seq1: &[u8], seq2: &[u8], scoring: &S) -> LocalAlignment {
+    let gap_score = scoring.gap_score();
+
+    // the first cell with the highest score, in row-major order
+    let (mut current_row, mut current_col, mut score) = (0, 0, 0);
+    for (row, matrix_row) in matrix.iter_rows().enumerate() {
+        for (col, &value) in matrix_row.iter().enumerate() {
+            if value > score {
+                (current_row, current_col, score) = (row, col, value);
+            }
+        }
+    }
+    let (seq1_end, seq2_end) = (current_col, current_row);
+
+    let mut alignment = AlignmentBuilder::with_capacity(seq1.len() + seq2.len());
+
+    while matrix[(current_row, current_col)] != 0 {
+        if current_col != 0 && matrix[(current_row, current_col)] == matrix[(current_row, current_col - 1)] + gap_score {
+            alignment.push_gap_in_seq2(seq1[current_col - 1]);
+            current_col -= 1;
+            continue;
+        }
+
+        if current_row != 0 && matrix[(current_row, current_col)] == matrix[(current_row - 1, current_col)] + gap_score {
+            alignment.push_gap_in_seq1(seq2[current_row - 1]);
+            current_row -= 1;
+            continue;
+        }
+
+        // diagonal case
+        alignment.push_aligned(seq1[current_col - 1], seq2[current_row - 1]);
+        current_row -= 1;
+        current_col -= 1;
+    }
+
+    LocalAlignment {
+        score,
+        seq1_start: current_col,
+        seq1_end,
+        seq2_start: current_row,
+        seq2_end,
+        alignment: alignment.finish(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use fasta_reader::read_fasta;
+    use scoring::SimpleScoring;
+
+    use crate::score_alignment;
+    use crate::smith_waterman::{backtrack_local_alignment, construct_local_matrix};
+
+    #[test]
+    fn test_local_read_in_sequence() {
+        // the read occurs with a single mismatch in the middle of the longer sequence
+        let seq1 = b"TTTTTTGATTACAGATTTTTT".to_vec();
+        let seq2 = b"CCGATTGCAGACC".to_vec();
+        let scoring = SimpleScoring::new(2, -1, -2);
+
+        let matrix = construct_local_matrix(&seq1, &seq2, &scoring);
+        let res = backtrack_local_alignment(&matrix, &seq1, &seq2, &scoring);
+        assert_eq!(res.score, 8 * 2 - 1);
+        assert_eq!((res.seq1_start, res.seq1_end), (6, 15));
+        assert_eq!((res.seq2_start, res.seq2_end), (2, 11));
+        assert_eq!(res.alignment, (b"GATTACAGA".to_vec(), b"||||*||||".to_vec(), b"GATTGCAGA".to_vec()));
+    }
+
+    #[test]
+    fn test_local_score_and_coordinates() -> Result<(), Box<dyn Error>> {
+        let (seq1, seq2) = read_fasta("../tests/verylarge.fasta")?;
+        let seq1_chars = seq1.into_bytes();
+        let seq2_chars = seq2.into_bytes();
+        let scoring = SimpleScoring::new(1, -1, -3);
+
+        let matrix = construct_local_matrix(&seq1_chars, &seq2_chars, &scoring);
+        let res = backtrack_local_alignment(&matrix, &seq1_chars, &seq2_chars, &scoring);
+        let (aligned_seq1, _, aligned_seq2) = &res.alignment;
+        assert_eq!(score_alignment(aligned_seq1, aligned_seq2, &scoring), res.score);
+        assert_eq!(aligned_seq1.iter().filter(|&&c| c != b'-').copied().collect::<Vec<u8>>(), seq1_chars[res.seq1_start..res.seq1_end]);
+        assert_eq!(aligned_seq2.iter().filter(|&&c| c != b'-').copied().collect::<Vec<u8>>(), seq2_chars[res.seq2_start..res.seq2_end]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_local_no_match() {
+        let scoring = SimpleScoring::new(1, -1, -1);
+        let matrix = construct_local_matrix(b"AAAA", b"CCC", &scoring);
+        let res = backtrack_local_alignment(&matrix, b"AAAA", b"CCC", &scoring);
+        assert_eq!(res.score, 0);
+        assert_eq!(res.alignment, (vec![], vec![], vec![]));
+
+        let matrix = construct_local_matrix(b"", b"CCC", &scoring);
+        assert_eq!(backtrack_local_alignment(&matrix, b"", b"CCC", &scoring).score, 0);
+    }
+}
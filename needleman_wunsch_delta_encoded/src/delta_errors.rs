@@ -0,0 +1,17 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+/// Error indicating that the deltas of the scoring scheme can fall outside the range of an `i8`
+#[derive(Debug, PartialEq, Eq)]
+pub struct DeltaOutOfRangeError {
+    pub min_delta: i32,
+    pub max_delta: i32,
+}
+
+impl Display for DeltaOutOfRangeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The deltas of the scoring scheme lie between {} and {}, which does not fit in the range {}..={} of the delta matrices", self.min_delta, self.max_delta, i8::MIN, i8::MAX)
+    }
+}
+
+impl Error for DeltaOutOfRangeError {}
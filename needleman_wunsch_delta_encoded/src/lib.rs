@@ -1,54 +1,162 @@
-pub fn construct_delta_matrices(seq1: &[u8], seq2: &[u8], match_score: i32, mismatch_score: i32, gap_score: i32) -> (Vec<Vec<Option<i32>>>, Vec<Vec<Option<i32>>>) {
-    let mut delta_v: Vec<Vec<Option<i32>>> = vec![vec![None; seq1.len() + 1]; seq2.len() + 1];
-    let mut delta_h: Vec<Vec<Option<i32>>> = vec![vec![None; seq1.len() + 1]; seq2.len() + 1];
+use std::cmp::max;
+
+use needleman_wunsch::{Alignment, AlignmentBuilder, DpMatrix};
+use scoring::Scoring;
+
+pub use crate::delta_errors::DeltaOutOfRangeError;
+
+mod delta_errors;
+
+/// Matrix of delta values with seq2 along the rows and seq1 along the columns, a `DpMatrix` with 1 byte per cell.
+/// The cells without a neighbour to compare with (the first column of delta h, the first row of delta v) are 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeltaMatrix {
+    deltas: DpMatrix<i8>,
+}
+
+impl DeltaMatrix {
+    fn new(rows: usize, cols: usize) -> DeltaMatrix {
+        DeltaMatrix { deltas: DpMatrix::new(rows, cols, 0) }
+    }
+
+    /// The delta in cell (row, col)
+    pub fn get(&self, row: usize, col: usize) -> i32 {
+        self.deltas[(row, col)] as i32
+    }
+
+    /// The caller guarantees that the delta fits in an `i8`, which `delta_bounds` proves for every cell
+    fn set(&mut self, row: usize, col: usize, delta: i32) {
+        debug_assert!(i8::try_from(delta).is_ok(), "Delta {} does not fit in an i8", delta);
+        self.deltas[(row, col)] = delta as i8;
+    }
+
+    pub fn rows(&self) -> usize {
+        self.deltas.rows()
+    }
+
+    pub fn cols(&self) -> usize {
+        self.deltas.cols()
+    }
+}
+
+/// The range every delta h and delta v value lies in when aligning seq1 and seq2: `(gap_score, max(gap_score, max_substitution_score - gap_score))`.
+/// A cell is at least the cell to its left (or above) plus the gap score. It is at most the cell diagonally above plus
+/// the best substitution score, which is in turn at most the cell to the left minus the gap score.
+/// The gaps from the cell above are bounded by induction on the rows, since the cell to the left is at least the cell diagonally above plus the gap score.
+pub fn delta_bounds<S: Scoring + ?Sized>(seq1: &[u8], seq2: &[u8], scoring: &S) -> (i32, i32) {
+    let mut seq1_alphabet = [false; 256];
+    let mut seq2_alphabet = [false; 256];
+    seq1.iter().for_each(|&c| seq1_alphabet[c as usize] = true);
+    seq2.iter().for_each(|&c| seq2_alphabet[c as usize] = true);
+
+    let gap_score = scoring.gap_score();
+    let mut max_delta = gap_score;
+    for a in (0..=255).filter(|&a| seq1_alphabet[a as usize]) {
+        for b in (0..=255).filter(|&b| seq2_alphabet[b as usize]) {
+            max_delta = max(max_delta, scoring.substitution_score(a, b) - gap_score);
+        }
+    }
+    (gap_score, max_delta)
+}
+
+/// Calculate the delta h and delta v matrices of the Needleman-Wunsch matrix.
+/// Returns an error if the deltas of the scoring scheme do not fit in an `i8`.
+pub fn construct_delta_matrices<S: Scoring + ?Sized>(seq1: &[u8], seq2: &[u8], scoring: &S) -> Result<(DeltaMatrix, DeltaMatrix), DeltaOutOfRangeError> {
+    let (min_delta, max_delta) = delta_bounds(seq1, seq2, scoring);
+    if min_delta < i8::MIN as i32 || max_delta > i8::MAX as i32 {
+        return Err(DeltaOutOfRangeError { min_delta, max_delta });
+    }
+
+    let gap_score = scoring.gap_score();
+    let mut delta_v = DeltaMatrix::new(seq2.len() + 1, seq1.len() + 1);
+    let mut delta_h = DeltaMatrix::new(seq2.len() + 1, seq1.len() + 1);
     // initialize the first row and column
-    delta_h[0] = (0..=seq1.len()).map(|_| Some(gap_score)).collect();
-    for index in 0..=seq2.len() {
-        delta_v[index][0] = Some(gap_score);
+    for col in 0..=seq1.len() {
+        delta_h.set(0, col, gap_score);
+    }
+    for row in 0..=seq2.len() {
+        delta_v.set(row, 0, gap_score);
     }
 
     for row in 1..=seq2.len() {
         let current_seq2_char = seq2[row - 1];
         for col in 1..=seq1.len() {
-            let cell_above = delta_h[row - 1][col].unwrap();
-            let cell_left = delta_v[row][col - 1].unwrap();
+            let cell_above = delta_h.get(row - 1, col);
+            let cell_left = delta_v.get(row, col - 1);
+
+            // the diagonal is optimal if its score minus the gap score beats both neighbouring deltas
+            let substitution_score = scoring.substitution_score(seq1[col - 1], current_seq2_char);
+            let diag_delta = substitution_score - gap_score;
 
             // fill in delta_v
-            delta_v[row][col] = if seq1[col - 1] == current_seq2_char {
-                Some(match_score - cell_above)
-            } else if mismatch_score - gap_score >= cell_above && mismatch_score - gap_score >= cell_left { // mismatch
-                Some(mismatch_score - cell_above)
-            } else if cell_above >= mismatch_score - gap_score && cell_above >= cell_left { // indel from above
-                Some(gap_score)
+            delta_v.set(row, col, if diag_delta >= cell_above && diag_delta >= cell_left { // match or mismatch
+                substitution_score - cell_above
+            } else if cell_above >= cell_left { // indel from above
+                gap_score
             } else { // indel from left
-                assert!(cell_left >= mismatch_score - gap_score && cell_left >= cell_above);
-                Some(cell_left + gap_score - cell_above)
-            };
+                cell_left + gap_score - cell_above
+            });
 
             // fill in delta_h
-            delta_h[row][col] = if seq1[col - 1] == current_seq2_char {
-                Some(match_score - cell_left)
-            } else if mismatch_score - gap_score >= cell_above && mismatch_score - gap_score >= cell_left {
-                Some(mismatch_score - cell_left)
-            } else if cell_above >= mismatch_score - gap_score && cell_above >= cell_left { // indel from above
-                Some(cell_above + gap_score - cell_left)
+            delta_h.set(row, col, if diag_delta >= cell_above && diag_delta >= cell_left { // match or mismatch
+                substitution_score - cell_left
+            } else if cell_above >= cell_left { // indel from above
+                cell_above + gap_score - cell_left
             } else { // indel from left
-                assert!(cell_left >= mismatch_score - gap_score && cell_left >= cell_above);
-                Some(gap_score)
-            };
+                gap_score
+            });
+        }
+    }
 
+    Ok((delta_h, delta_v))
+}
+
+/// The score of the optimal alignment: the first column of the last row plus the delta h values of the last row
+pub fn delta_score<S: Scoring + ?Sized>(delta_h: &DeltaMatrix, scoring: &S) -> i32 {
+    let last_row = delta_h.rows() - 1;
+    last_row as i32 * scoring.gap_score() + (1..delta_h.cols()).map(|col| delta_h.get(last_row, col)).sum::<i32>()
+}
+
+/// Find an optimal alignment by walking back through the delta matrices, without calculating the absolute scores.
+/// A gap is possible when the delta in its direction equals the gap score,
+/// the diagonal is possible when the delta v of the cell plus the delta h of the cell above equals the substitution score.
+/// Ties are broken in the same way as `needleman_wunsch::backtrack_alignment`.
+pub fn backtrack_delta_alignment<S: Scoring + ?Sized>(delta_h: &DeltaMatrix, delta_v: &DeltaMatrix, seq1: &[u8], seq2: &[u8], scoring: &S) -> Alignment {
+    let gap_score = scoring.gap_score();
+    let mut current_row = seq2.len();
+    let mut current_col = seq1.len();
+
+    let mut alignment = AlignmentBuilder::with_capacity(seq1.len() + seq2.len());
+
+    while current_col != 0 || current_row != 0 {
+        if current_col != 0 && (current_row == 0 || delta_h.get(current_row, current_col) == gap_score) {
+            alignment.push_gap_in_seq2(seq1[current_col - 1]);
+            current_col -= 1;
+        } else if current_row != 0 && (current_col == 0 || delta_v.get(current_row, current_col) == gap_score) {
+            alignment.push_gap_in_seq1(seq2[current_row - 1]);
+            current_row -= 1;
+        } else {
+            // diagonal case
+            debug_assert_eq!(
+                delta_v.get(current_row, current_col) + delta_h.get(current_row - 1, current_col),
+                scoring.substitution_score(seq1[current_col - 1], seq2[current_row - 1])
+            );
+            alignment.push_aligned(seq1[current_col - 1], seq2[current_row - 1]);
+            current_row -= 1;
+            current_col -= 1;
         }
     }
 
-    (delta_h, delta_v)
+    alignment.finish()
 }
 
 #[cfg(test)]
 mod tests {
     use std::error::Error;
     use fasta_reader::read_fasta;
-    use needleman_wunsch::construct_matrix;
-    use crate::construct_delta_matrices;
+    use needleman_wunsch::{backtrack_alignment, construct_matrix, DpMatrix};
+    use scoring::SimpleScoring;
+    use crate::{backtrack_delta_alignment, construct_delta_matrices, delta_bounds, delta_score, DeltaOutOfRangeError};
 
     #[test]
     fn test_delta_h_and_v_matrix_equal_to_s_matrix() -> Result<(), Box<dyn Error>> {
@@ -56,32 +164,30 @@ mod tests {
         let seq1_chars = seq1.into_bytes();
         let seq2_chars = seq2.into_bytes();
 
-        let match_score = 1;
-        let mismatch_score = -1;
         let gap_score = -3;
+        let scoring = SimpleScoring::new(1, -1, gap_score);
 
-        let (delta_h, delta_v) = construct_delta_matrices(&seq1_chars, &seq2_chars, match_score, mismatch_score, gap_score);
+        let (delta_h, delta_v) = construct_delta_matrices(&seq1_chars, &seq2_chars, &scoring)?;
 
-        let s_matrix = construct_matrix(&seq1_chars, &seq2_chars, match_score, mismatch_score, gap_score);
+        let s_matrix = construct_matrix(&seq1_chars, &seq2_chars, &scoring);
 
         // test delta_h matrix
-        let mut s_matrix_from_h : Vec<Vec<i32>> = vec![vec![0; seq1_chars.len() + 1]; seq2_chars.len() + 1];
-        for (index, init_value) in (0..=seq2_chars.len()).enumerate() {
-            s_matrix_from_h[index][0] = init_value as i32 * gap_score;
-        }
+        let mut s_matrix_from_h = DpMatrix::new(seq2_chars.len() + 1, seq1_chars.len() + 1, 0);
         for row in 0..=seq2_chars.len() {
+            s_matrix_from_h[(row, 0)] = row as i32 * gap_score;
             for col in 1..=seq1_chars.len() {
-                s_matrix_from_h[row][col] = s_matrix_from_h[row][col-1] + delta_h[row][col].ok_or("Value should have been filled in")?;
+                s_matrix_from_h[(row, col)] = s_matrix_from_h[(row, col - 1)] + delta_h.get(row, col);
             }
         }
         assert_eq!(s_matrix, s_matrix_from_h);
 
-        let mut s_matrix_from_v : Vec<Vec<i32>> = vec![vec![0; seq1_chars.len() + 1]; seq2_chars.len() + 1];
-        s_matrix_from_v[0] = (0..=seq1_chars.len()).map(|i| i as i32 * gap_score).collect();
-
+        let mut s_matrix_from_v = DpMatrix::new(seq2_chars.len() + 1, seq1_chars.len() + 1, 0);
+        for col in 0..=seq1_chars.len() {
+            s_matrix_from_v[(0, col)] = col as i32 * gap_score;
+        }
         for row in 1..=seq2_chars.len() {
             for col in 0..=seq1_chars.len() {
-                s_matrix_from_v[row][col] = s_matrix_from_v[row-1][col] + delta_v[row][col].ok_or("Value should have been filled in")?;
+                s_matrix_from_v[(row, col)] = s_matrix_from_v[(row - 1, col)] + delta_v.get(row, col);
             }
         }
         assert_eq!(s_matrix, s_matrix_from_v);
@@ -89,4 +195,64 @@ mod tests {
         Ok(())
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_backtrack_delta_alignment() -> Result<(), Box<dyn Error>> {
+        for filename in ["../tests/input.fasta", "../tests/dna_input.fasta", "../tests/protein_input.fasta"] {
+            let (seq1, seq2) = read_fasta(filename)?;
+            let seq1_chars = seq1.into_bytes();
+            let seq2_chars = seq2.into_bytes();
+
+            for scoring in [SimpleScoring::new(1, -1, -3), SimpleScoring::new(2, -1, -1), SimpleScoring::new(0, -1, -1)] {
+                let (delta_h, delta_v) = construct_delta_matrices(&seq1_chars, &seq2_chars, &scoring)?;
+                let s_matrix = construct_matrix(&seq1_chars, &seq2_chars, &scoring);
+
+                assert_eq!(delta_score(&delta_h, &scoring), s_matrix[(seq2_chars.len(), seq1_chars.len())]);
+                let alignment = backtrack_delta_alignment(&delta_h, &delta_v, &seq1_chars, &seq2_chars, &scoring);
+                assert_eq!(alignment, backtrack_alignment(&s_matrix, &seq1_chars, &seq2_chars, &scoring));
+            }
+        }
+
+        let scoring = SimpleScoring::new(1, -1, -1);
+        let (delta_h, delta_v) = construct_delta_matrices(b"", b"ACG", &scoring)?;
+        assert_eq!(delta_score(&delta_h, &scoring), -3);
+        assert_eq!(backtrack_delta_alignment(&delta_h, &delta_v, b"", b"ACG", &scoring), (b"---".to_vec(), b"   ".to_vec(), b"ACG".to_vec()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_delta_bounds() -> Result<(), Box<dyn Error>> {
+        let (seq1, seq2) = read_fasta("../tests/dna_input.fasta")?;
+        let seq1_chars = seq1.into_bytes();
+        let seq2_chars = seq2.into_bytes();
+
+        for scoring in [SimpleScoring::new(1, -1, -3), SimpleScoring::new(5, -4, -10), SimpleScoring::new(1, -1, 2)] {
+            let (min_delta, max_delta) = delta_bounds(&seq1_chars, &seq2_chars, &scoring);
+            let (delta_h, delta_v) = construct_delta_matrices(&seq1_chars, &seq2_chars, &scoring)?;
+            // the first column of delta h and the first row of delta v lie outside the matrix
+            for row in 0..delta_h.rows() {
+                for col in 1..delta_h.cols() {
+                    assert!((min_delta..=max_delta).contains(&delta_h.get(row, col)));
+                }
+            }
+            for row in 1..delta_v.rows() {
+                for col in 0..delta_v.cols() {
+                    assert!((min_delta..=max_delta).contains(&delta_v.get(row, col)));
+                }
+            }
+        }
+        assert_eq!(delta_bounds(&seq1_chars, &seq2_chars, &SimpleScoring::new(1, -1, -3)), (-3, 4));
+
+        // the deltas of these scores don't fit in an i8
+        let scoring = SimpleScoring::new(100, -1, -100);
+        assert_eq!(construct_delta_matrices(&seq1_chars, &seq2_chars, &scoring), Err(DeltaOutOfRangeError { min_delta: -100, max_delta: 200 }));
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_delta_matrix_column_out_of_bounds() {
+        let (delta_h, _) = construct_delta_matrices(b"ACG", b"AC", &SimpleScoring::new(1, -1, -3)).unwrap();
+        delta_h.get(0, delta_h.cols());
+    }
+}
@@ -1,14 +1,20 @@
 use std::error::Error;
+use std::str::from_utf8;
 use clap::Parser;
-use fasta_reader::read_fasta;
-use needleman_wunsch_delta_encoded::construct_delta_matrices;
+use fasta_reader::{read_fasta, read_fasta_regions};
+use needleman_wunsch_delta_encoded::{backtrack_delta_alignment, construct_delta_matrices, delta_score};
+use scoring::SimpleScoring;
 
 #[derive(Parser, Debug)]
 #[clap(allow_negative_numbers = true)]
 struct Args {
-    /// The input file name
+    /// The input file name (FASTA or FASTQ)
     #[clap(short, long)]
     filename: String,
+    /// Fetch the 2 sequences to align from the indexed input file instead of using the first 2 records.
+    /// Each region is written as `name`, `name:start` or `name:start-end` (1-based, inclusive), e.g. `--region chr1:1000-2000 read1`
+    #[clap(short, long, num_args = 2, value_names = ["REGION1", "REGION2"])]
+    region: Option<Vec<String>>,
     /// The score used when there is a match
     #[clap(short, long, default_value_t = 1)]
     match_score: i32,
@@ -22,27 +28,24 @@ struct Args {
 
 fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
-    let Args { filename, match_score, mismatch_score, gap_score } = args;
-    let (seq1, seq2) = read_fasta(&filename)?;
+    let Args { filename, region, match_score, mismatch_score, gap_score } = args;
+    let (seq1, seq2) = match region {
+        Some(regions) => read_fasta_regions(&filename, &regions[0], &regions[1])?,
+        None => read_fasta(&filename)?,
+    };
     let seq1_chars = seq1.into_bytes();
     let seq2_chars = seq2.into_bytes();
 
-    let (delta_h, delta_v) = construct_delta_matrices(&seq1_chars, &seq2_chars, match_score, mismatch_score, gap_score);
+    let scoring = SimpleScoring::new(match_score, mismatch_score, gap_score);
+    let (delta_h, delta_v) = construct_delta_matrices(&seq1_chars, &seq2_chars, &scoring)?;
+    println!("The score for optimal alignment is: {}", delta_score(&delta_h, &scoring));
 
-    let row = seq2_chars.len();
-    let mut delta_h_score = row as i32 * gap_score;
-    for col in 1..=seq1_chars.len() {
-        delta_h_score += delta_h[row][col].unwrap();
-    }
-
-    let col = seq1_chars.len();
-    let mut delta_v_score = col as i32 * gap_score;
-    for row in 1..=seq2_chars.len() {
-        delta_v_score += delta_v[row][col].unwrap();
-    }
-
-    println!("score according to the delta h matrix {}", delta_h_score);
-    println!("score according to the delta v matrix {}", delta_v_score);
+    let (aligned_seq1, diff_line, aligned_seq2) = backtrack_delta_alignment(&delta_h, &delta_v, &seq1_chars, &seq2_chars, &scoring);
+    println!();
+    println!("Aligned sequences:");
+    println!("{}", from_utf8(&aligned_seq1)?);
+    println!("{}", from_utf8(&diff_line)?);
+    println!("{}", from_utf8(&aligned_seq2)?);
 
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,69 @@
+pub use crate::scoring_errors::ScoringError;
+pub use crate::substitution_matrix::SubstitutionMatrix;
+
+mod scoring_errors;
+mod substitution_matrix;
+
+/// A scoring scheme for pairwise alignment: how much aligning 2 characters and inserting a gap contribute to the score
+pub trait Scoring {
+    /// The score of aligning character `a` of the first sequence with character `b` of the second sequence
+    fn substitution_score(&self, a: u8, b: u8) -> i32;
+
+    /// The score of aligning a single character with a gap
+    fn gap_score(&self) -> i32;
+
+    /// The extra score for opening a gap, used by the affine gap aligners: a gap of length k scores `gap_open_score + k * gap_score`.
+    /// The linear gap aligners ignore this score.
+    fn gap_open_score(&self) -> i32 {
+        0
+    }
+}
+
+/// Scoring scheme with a fixed score for a match, a mismatch and a gap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SimpleScoring {
+    pub match_score: i32,
+    pub mismatch_score: i32,
+    pub gap_score: i32,
+    pub gap_open_score: i32,
+}
+
+impl SimpleScoring {
+    pub fn new(match_score: i32, mismatch_score: i32, gap_score: i32) -> SimpleScoring {
+        SimpleScoring { match_score, mismatch_score, gap_score, gap_open_score: 0 }
+    }
+
+    /// Use affine gaps, where opening a gap scores an extra `gap_open_score`
+    pub fn with_gap_open_score(self, gap_open_score: i32) -> SimpleScoring {
+        SimpleScoring { gap_open_score, ..self }
+    }
+}
+
+impl Scoring for SimpleScoring {
+    fn substitution_score(&self, a: u8, b: u8) -> i32 {
+        if a == b { self.match_score } else { self.mismatch_score }
+    }
+
+    fn gap_score(&self) -> i32 {
+        self.gap_score
+    }
+
+    fn gap_open_score(&self) -> i32 {
+        self.gap_open_score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Scoring, SimpleScoring};
+
+    #[test]
+    fn test_simple_scoring() {
+        let scoring = SimpleScoring::new(1, -1, -3);
+        assert_eq!(scoring.substitution_score(b'A', b'A'), 1);
+        assert_eq!(scoring.substitution_score(b'A', b'C'), -1);
+        assert_eq!(scoring.gap_score(), -3);
+        assert_eq!(scoring.gap_open_score(), 0);
+        assert_eq!(scoring.with_gap_open_score(-10).gap_open_score(), -10);
+    }
+}
@@ -0,0 +1,49 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io;
+
+/// Error indicating that a substitution matrix could not be loaded.
+/// All line numbers are 1-based.
+#[derive(Debug)]
+pub enum ScoringError {
+    /// The matrix file could not be read
+    Io(io::Error),
+    /// The name is neither a built-in matrix nor an existing file
+    UnknownMatrix { name: String },
+    /// The matrix does not contain a header line with the residues of the columns
+    MissingHeader,
+    /// A residue occurs more than once in the header
+    DuplicateResidue { residue: u8 },
+    /// The row does not start with a residue from the header or does not contain a valid score for every column
+    InvalidRow { line: usize },
+    /// The matrix does not contain a row for every residue in the header
+    MissingRows { expected: usize, found: usize },
+}
+
+impl Display for ScoringError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScoringError::Io(error) => write!(f, "Failed to read the substitution matrix: {}", error),
+            ScoringError::UnknownMatrix { name } => write!(f, "'{}' is neither a built-in substitution matrix nor an existing file", name),
+            ScoringError::MissingHeader => write!(f, "The substitution matrix does not contain a header with the residues"),
+            ScoringError::DuplicateResidue { residue } => write!(f, "Residue '{}' occurs more than once in the substitution matrix", residue.escape_ascii()),
+            ScoringError::InvalidRow { line } => write!(f, "Line {} of the substitution matrix is not a valid row", line),
+            ScoringError::MissingRows { expected, found } => write!(f, "The substitution matrix has {} columns, but only {} rows", expected, found),
+        }
+    }
+}
+
+impl Error for ScoringError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ScoringError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ScoringError {
+    fn from(error: io::Error) -> Self {
+        ScoringError::Io(error)
+    }
+}
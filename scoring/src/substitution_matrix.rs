@@ -0,0 +1,209 @@
+use std::fs;
+use std::path::Path;
+
+use crate::scoring_errors::ScoringError;
+use crate::Scoring;
+
+/// The substitution matrices that are included in the binary, in the NCBI format
+const BUILTIN_MATRICES: [(&str, &str); 4] = [
+    ("BLOSUM62", include_str!("../matrices/BLOSUM62")),
+    ("PAM40", include_str!("../matrices/PAM40")),
+    ("PAM120", include_str!("../matrices/PAM120")),
+    ("PAM250", include_str!("../matrices/PAM250")),
+];
+
+/// Scoring scheme that looks up the score of every pair of residues in a substitution matrix (e.g. BLOSUM62)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubstitutionMatrix {
+    /// The residues of the rows and columns, in order
+    alphabet: Vec<u8>,
+    /// Maps every byte to its row/column in `scores`.
+    /// Residues that are not in the alphabet map to `X` if the matrix has it, or to an extra row/column otherwise.
+    indices: [usize; 256],
+    /// The number of rows (and columns) in `scores`
+    size: usize,
+    /// The scores in row-major order
+    scores: Vec<i32>,
+    gap_score: i32,
+    gap_open_score: i32,
+}
+
+impl SubstitutionMatrix {
+    /// Parse a substitution matrix in the NCBI format: a header line with the residues, followed by one row per residue that starts with that residue.
+    /// Lines starting with `#` are comments.
+    pub fn parse(content: &str, gap_score: i32) -> Result<SubstitutionMatrix, ScoringError> {
+        let mut lines = content.lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+        let (_, header) = lines.next().ok_or(ScoringError::MissingHeader)?;
+        let mut alphabet = vec![];
+        for residue in header.split_whitespace() {
+            let &[residue] = residue.as_bytes() else {
+                return Err(ScoringError::MissingHeader);
+            };
+            if alphabet.contains(&residue) {
+                return Err(ScoringError::DuplicateResidue { residue });
+            }
+            alphabet.push(residue);
+        }
+
+        let size = alphabet.len();
+        let mut rows: Vec<Option<Vec<i32>>> = vec![None; size];
+        for (line_number, line) in lines {
+            let invalid_row = || ScoringError::InvalidRow { line: line_number };
+            let mut fields = line.split_whitespace();
+            let residue = fields.next().ok_or_else(invalid_row)?;
+            let row_index = alphabet.iter().position(|&letter| residue.as_bytes() == [letter]).ok_or_else(invalid_row)?;
+            let row = fields.map(|score| score.parse::<i32>()).collect::<Result<Vec<i32>, _>>().map_err(|_| invalid_row())?;
+            if row.len() != size || rows[row_index].is_some() {
+                return Err(invalid_row());
+            }
+            rows[row_index] = Some(row);
+        }
+
+        let found = rows.iter().filter(|row| row.is_some()).count();
+        if found != size {
+            return Err(ScoringError::MissingRows { expected: size, found });
+        }
+
+        Ok(SubstitutionMatrix::from_rows(alphabet, rows.into_iter().flatten().collect(), gap_score))
+    }
+
+    fn from_rows(alphabet: Vec<u8>, rows: Vec<Vec<i32>>, gap_score: i32) -> SubstitutionMatrix {
+        let unknown_index = alphabet.iter().position(|&residue| residue == b'X');
+        // add an extra row and column for unknown residues if the matrix has no X, scored with the worst substitution
+        let size = if unknown_index.is_some() { alphabet.len() } else { alphabet.len() + 1 };
+        let min_score = rows.iter().flatten().copied().min().unwrap_or(0);
+
+        let mut scores = vec![min_score; size * size];
+        for (row_index, row) in rows.iter().enumerate() {
+            scores[row_index * size..row_index * size + row.len()].copy_from_slice(row);
+        }
+
+        let mut indices = [unknown_index.unwrap_or(alphabet.len()); 256];
+        for (index, &residue) in alphabet.iter().enumerate() {
+            indices[residue.to_ascii_lowercase() as usize] = index;
+        }
+        // an exact match takes precedence over the lowercase variant, for matrices that use both cases
+        for (index, &residue) in alphabet.iter().enumerate() {
+            indices[residue as usize] = index;
+        }
+
+        SubstitutionMatrix { alphabet, indices, size, scores, gap_score, gap_open_score: 0 }
+    }
+
+    /// Read a substitution matrix in the NCBI format from the file at `path`
+    pub fn from_file<P: AsRef<Path>>(path: P, gap_score: i32) -> Result<SubstitutionMatrix, ScoringError> {
+        SubstitutionMatrix::parse(&fs::read_to_string(path)?, gap_score)
+    }
+
+    /// The built-in matrix with the given name (case-insensitive): BLOSUM62, PAM40, PAM120 or PAM250
+    pub fn builtin(name: &str, gap_score: i32) -> Option<SubstitutionMatrix> {
+        BUILTIN_MATRICES.iter()
+            .find(|(builtin_name, _)| builtin_name.eq_ignore_ascii_case(name))
+            .map(|(_, content)| SubstitutionMatrix::parse(content, gap_score).expect("Built-in substitution matrices are valid"))
+    }
+
+    /// The built-in matrix with the given name, or otherwise the matrix stored in the file with that name
+    pub fn from_name_or_file(name: &str, gap_score: i32) -> Result<SubstitutionMatrix, ScoringError> {
+        if let Some(matrix) = SubstitutionMatrix::builtin(name, gap_score) {
+            return Ok(matrix);
+        }
+        if !Path::new(name).is_file() {
+            return Err(ScoringError::UnknownMatrix { name: name.to_string() });
+        }
+        SubstitutionMatrix::from_file(name, gap_score)
+    }
+
+    pub fn blosum62(gap_score: i32) -> SubstitutionMatrix {
+        SubstitutionMatrix::builtin("BLOSUM62", gap_score).unwrap()
+    }
+
+    pub fn pam250(gap_score: i32) -> SubstitutionMatrix {
+        SubstitutionMatrix::builtin("PAM250", gap_score).unwrap()
+    }
+
+    /// Use affine gaps, where opening a gap scores an extra `gap_open_score`
+    pub fn with_gap_open_score(self, gap_open_score: i32) -> SubstitutionMatrix {
+        SubstitutionMatrix { gap_open_score, ..self }
+    }
+
+    /// The residues of the rows and columns of the matrix
+    pub fn alphabet(&self) -> &[u8] {
+        &self.alphabet
+    }
+}
+
+impl Scoring for SubstitutionMatrix {
+    fn substitution_score(&self, a: u8, b: u8) -> i32 {
+        self.scores[self.indices[a as usize] * self.size + self.indices[b as usize]]
+    }
+
+    fn gap_score(&self) -> i32 {
+        self.gap_score
+    }
+
+    fn gap_open_score(&self) -> i32 {
+        self.gap_open_score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scoring_errors::ScoringError;
+    use crate::substitution_matrix::SubstitutionMatrix;
+    use crate::Scoring;
+
+    #[test]
+    fn test_blosum62() {
+        let matrix = SubstitutionMatrix::blosum62(-4);
+        assert_eq!(matrix.alphabet().len(), 24);
+        assert_eq!(matrix.substitution_score(b'W', b'W'), 11);
+        assert_eq!(matrix.substitution_score(b'A', b'R'), -1);
+        assert_eq!(matrix.substitution_score(b'R', b'A'), -1);
+        assert_eq!(matrix.substitution_score(b'c', b'C'), 9);
+        // unknown residues are scored as X
+        assert_eq!(matrix.substitution_score(b'J', b'A'), 0);
+        assert_eq!(matrix.gap_score(), -4);
+    }
+
+    #[test]
+    fn test_builtin_matrices_symmetric() {
+        for name in ["BLOSUM62", "PAM40", "PAM120", "PAM250"] {
+            let matrix = SubstitutionMatrix::builtin(name, -1).unwrap();
+            for &a in matrix.alphabet() {
+                for &b in matrix.alphabet() {
+                    assert_eq!(matrix.substitution_score(a, b), matrix.substitution_score(b, a));
+                }
+            }
+        }
+        assert_eq!(SubstitutionMatrix::pam250(-1).substitution_score(b'W', b'W'), 17);
+        assert!(SubstitutionMatrix::builtin("blosum62", -1).is_some());
+        assert!(SubstitutionMatrix::builtin("BLOSUM99", -1).is_none());
+    }
+
+    #[test]
+    fn test_parse() -> Result<(), ScoringError> {
+        let matrix = SubstitutionMatrix::parse("# small DNA matrix\n   A  C\nC -1  2\nA  1 -1\n", -2)?;
+        assert_eq!(matrix.alphabet(), b"AC");
+        assert_eq!(matrix.substitution_score(b'A', b'A'), 1);
+        assert_eq!(matrix.substitution_score(b'C', b'C'), 2);
+        assert_eq!(matrix.substitution_score(b'A', b'C'), -1);
+        // no X in the matrix, so unknown residues get the lowest score
+        assert_eq!(matrix.substitution_score(b'A', b'N'), -1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(matches!(SubstitutionMatrix::parse("# only comments\n", -1), Err(ScoringError::MissingHeader)));
+        assert!(matches!(SubstitutionMatrix::parse("A A\nA 1 1\n", -1), Err(ScoringError::DuplicateResidue { residue: b'A' })));
+        assert!(matches!(SubstitutionMatrix::parse("A C\nA 1\nC 0 1\n", -1), Err(ScoringError::InvalidRow { line: 2 })));
+        assert!(matches!(SubstitutionMatrix::parse("A C\nA 1 x\nC 0 1\n", -1), Err(ScoringError::InvalidRow { line: 2 })));
+        assert!(matches!(SubstitutionMatrix::parse("A C\nA 1 0\n", -1), Err(ScoringError::MissingRows { expected: 2, found: 1 })));
+        assert!(matches!(SubstitutionMatrix::from_name_or_file("NOT_A_MATRIX", -1), Err(ScoringError::UnknownMatrix { .. })));
+    }
+}